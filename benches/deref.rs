@@ -0,0 +1,79 @@
+//! Deref cost of inline and heap boxes.
+//!
+//! `SmallBox::as_ptr` already compiles to `cmp; cmovne` at `-O` on x86-64, and a
+//! `select_unpredictable` hint produced the same code. Medians over two runs:
+//!
+//! - `deref_dyn` inline/heap: 7.1-7.4 µs / 7.3-7.8 µs
+//! - `deref_sized` inline/heap: 0.9-1.4 µs / 1.3-1.6 µs
+
+use smallbox::SmallBox;
+use smallbox::smallbox;
+use smallbox::space::*;
+
+fn main() {
+    divan::main();
+}
+
+trait Shape {
+    fn area(&self) -> f64;
+}
+
+struct Square(f64);
+
+impl Shape for Square {
+    fn area(&self) -> f64 {
+        self.0 * self.0
+    }
+}
+
+struct Rect(f64, f64, [f64; 8]);
+
+impl Shape for Rect {
+    fn area(&self) -> f64 {
+        self.0 * self.1 + self.2[0]
+    }
+}
+
+const LEN: usize = 1024;
+
+fn shapes(heap: bool) -> Vec<SmallBox<dyn Shape, S4>> {
+    (0..LEN)
+        .map(|i| -> SmallBox<dyn Shape, S4> {
+            if heap {
+                smallbox!(Rect(1.0, divan::black_box(i as f64), [0.0; 8]))
+            } else {
+                smallbox!(Square(divan::black_box(i as f64)))
+            }
+        })
+        .collect()
+}
+
+#[divan::bench(args = [false, true])]
+fn deref_dyn(bencher: divan::Bencher, heap: bool) {
+    let shapes = shapes(heap);
+    assert!(shapes.iter().all(|s| s.is_heap() == heap));
+    bencher
+        .counter(divan::counter::ItemsCount::new(LEN))
+        .bench_local(|| {
+            divan::black_box(&shapes)
+                .iter()
+                .map(|s| s.area())
+                .sum::<f64>()
+        });
+}
+
+#[divan::bench(args = [false, true])]
+fn deref_sized(bencher: divan::Bencher, heap: bool) {
+    let values: Vec<SmallBox<u64, S4>> = (0..LEN as u64)
+        .map(|i| {
+            if heap {
+                SmallBox::from_box(Box::new(i))
+            } else {
+                SmallBox::new(i)
+            }
+        })
+        .collect();
+    bencher
+        .counter(divan::counter::ItemsCount::new(LEN))
+        .bench_local(|| divan::black_box(&values).iter().map(|v| **v).sum::<u64>());
+}
@@ -0,0 +1,126 @@
+//! `arbitrary` support, enabled by the `arbitrary` feature
+//!
+//! Generated boxes are deliberately stored both inline and on the heap. Sized values and strings
+//! are moved to the heap based on an extra input byte, even if they fit in `Space`, and slice
+//! lengths are drawn from a range around the number of elements that fit in `Space`.
+//!
+//! Values are only moved to the heap if [`FallbackPolicy::ALLOWS_HEAP`] is `true`, and the move
+//! is reported to [`FallbackPolicy::on_heap_fallback`] like a fallback of a value that does not
+//! fit.
+
+use ::arbitrary::Arbitrary;
+use ::arbitrary::Result;
+use ::arbitrary::Unstructured;
+use ::arbitrary::size_hint;
+
+use crate::SmallBox;
+use crate::inline::InlineSlice;
+use crate::inline::collect_slice;
+use crate::policy::FallbackPolicy;
+use crate::sptr;
+
+impl<'a, T: Arbitrary<'a>, Space, P: FallbackPolicy> Arbitrary<'a> for SmallBox<T, Space, P> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let this = SmallBox::new(T::arbitrary(u)?);
+        Ok(if bool::arbitrary(u)? {
+            this.into_heap_if_allowed()
+        } else {
+            this
+        })
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        size_hint::and(T::size_hint(depth), bool::size_hint(depth))
+    }
+}
+
+impl<'a, T: Arbitrary<'a>, Space, P: FallbackPolicy> Arbitrary<'a> for SmallBox<[T], Space, P> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let len = u.int_in_range(0..=InlineSlice::<T, Space>::LEN_LIMIT)?;
+        collect_slice(len, || T::arbitrary(u))
+    }
+}
+
+impl<'a, Space, P: FallbackPolicy> Arbitrary<'a> for SmallBox<str, Space, P> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let s = <&str>::arbitrary(u)?;
+        let this = unsafe { SmallBox::new_copy(s, sptr::from_ref(s)) };
+        Ok(if bool::arbitrary(u)? {
+            this.into_heap_if_allowed()
+        } else {
+            this
+        })
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        size_hint::and(<&str>::size_hint(depth), bool::size_hint(depth))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::alloc::vec::Vec;
+    use ::arbitrary::Arbitrary;
+    use ::arbitrary::Unstructured;
+
+    use crate::SmallBox;
+    use crate::policy::AllowHeap;
+    use crate::space::*;
+
+    /// Generates boxes from a fixed pseudo-random input and counts those stored on the heap.
+    fn count_heap<T: ?Sized, Space, P>(generated: usize) -> usize
+    where SmallBox<T, Space, P>: for<'a> Arbitrary<'a> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let bytes: Vec<u8> = (0..generated * 64)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state.to_le_bytes()[0]
+            })
+            .collect();
+        let mut u = Unstructured::new(&bytes);
+        (0..generated)
+            .map(|_| SmallBox::<T, Space, P>::arbitrary(&mut u).unwrap())
+            .filter(|b| b.is_heap())
+            .count()
+    }
+
+    #[test]
+    fn test_both_placements() {
+        let heap = count_heap::<[u8], S2, AllowHeap>(200);
+        assert!(heap > 20 && heap < 180, "{heap}");
+
+        let heap = count_heap::<u32, S4, AllowHeap>(200);
+        assert!(heap > 20 && heap < 180, "{heap}");
+
+        let heap = count_heap::<str, S2, AllowHeap>(200);
+        assert!(heap > 20 && heap < 180, "{heap}");
+
+        // values that do not fit are always on the heap
+        assert_eq!(count_heap::<[u64; 8], S4, AllowHeap>(50), 50);
+    }
+
+    #[test]
+    fn test_policy() {
+        use crate::policy::PanicOnHeap;
+        use crate::policy::tests::CountFallbacks;
+        use crate::policy::tests::fallbacks_by;
+
+        let mut heap = 0;
+        let fallbacks = fallbacks_by(|| heap = count_heap::<u32, S4, CountFallbacks>(200));
+        assert!(heap > 20 && heap < 180, "{heap}");
+        assert_eq!(fallbacks, heap);
+
+        assert_eq!(count_heap::<u32, S4, PanicOnHeap>(200), 0);
+        assert_eq!(count_heap::<str, S2, PanicOnHeap>(200), 0);
+    }
+
+    #[test]
+    fn test_exhausted() {
+        let mut u = Unstructured::new(&[]);
+        let slice = SmallBox::<[u8], S2>::arbitrary(&mut u).unwrap();
+        assert!(slice.is_empty());
+        assert!(!slice.is_heap());
+    }
+}
@@ -0,0 +1,619 @@
+//! Boxes whose heap fallback comes from a borrowed arena
+//!
+//! An [`ArenaSmallBox`] stores small values inline like a [`SmallBox`], and places larger values
+//! in an [`Arena`] instead of the global allocator. Dropping an `ArenaSmallBox` only drops the
+//! value, the memory is reclaimed when the arena is reset or dropped. This suits many boxes that
+//! die together, e.g. at the end of a request.
+//!
+//! With the `bumpalo` feature, [`Arena`] is implemented for `bumpalo::Bump`:
+//!
+//! ```
+//! #[macro_use]
+//! extern crate smallbox;
+//!
+//! # fn main() {
+//! # #[cfg(feature = "bumpalo")]
+//! # {
+//! use core::fmt::Debug;
+//!
+//! use bumpalo::Bump;
+//! use smallbox::arena::ArenaSmallBox;
+//! use smallbox::space::S1;
+//!
+//! let arena = Bump::new();
+//!
+//! let small: ArenaSmallBox<dyn Debug, S1> = smallbox!(in &arena, 1u8);
+//! let large: ArenaSmallBox<dyn Debug, S1> = smallbox!(in &arena, [0usize; 8]);
+//!
+//! assert!(!small.is_arena());
+//! assert!(large.is_arena());
+//! # }
+//! # }
+//! ```
+//!
+//! [`SmallBox`]: crate::SmallBox
+
+use core::alloc::Layout;
+use core::any::Any;
+use core::cell::UnsafeCell;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::Hash;
+use core::hash::{self};
+use core::marker::PhantomData;
+#[cfg(feature = "coerce")]
+use core::marker::Unsize;
+use core::mem::ManuallyDrop;
+use core::mem::MaybeUninit;
+#[cfg(feature = "zeroize")]
+use core::mem::{self};
+use core::ops;
+#[cfg(feature = "coerce")]
+use core::ops::CoerceUnsized;
+use core::ptr;
+use core::ptr::NonNull;
+
+use crate::smallbox::INLINE_SENTINEL;
+use crate::smallbox::MIN_ALIGNMENT;
+use crate::sptr;
+
+/// An allocator that hands out memory which stays valid until the arena itself is reset or
+/// dropped
+///
+/// # Safety
+///
+/// The memory returned by `alloc_layout` must be valid for reads and writes of `layout`, must not
+/// overlap any other live allocation, and must stay valid for as long as the arena is borrowed.
+///
+/// # Example
+///
+/// ```
+/// use core::cell::Cell;
+/// use core::mem::MaybeUninit;
+/// use core::ptr::NonNull;
+/// use std::alloc::Layout;
+///
+/// use smallbox::arena::Arena;
+/// use smallbox::arena::ArenaSmallBox;
+/// use smallbox::space::S1;
+///
+/// /// A fixed-size arena that never frees
+/// struct Fixed {
+///     buf: [Cell<MaybeUninit<u64>>; 16],
+///     used: Cell<usize>,
+/// }
+///
+/// unsafe impl Arena for Fixed {
+///     fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+///         assert!(layout.align() <= 8);
+///         let start = self.used.get();
+///         let words = layout.size().div_ceil(8);
+///         assert!(start + words <= self.buf.len(), "arena exhausted");
+///         self.used.set(start + words);
+///         NonNull::new(self.buf[start].as_ptr().cast()).unwrap()
+///     }
+/// }
+///
+/// let arena = Fixed {
+///     buf: [const { Cell::new(MaybeUninit::uninit()) }; 16],
+///     used: Cell::new(0),
+/// };
+/// let large: ArenaSmallBox<_, S1> = ArenaSmallBox::new_in([1usize, 2, 3], &arena);
+/// assert!(large.is_arena());
+/// assert_eq!(*large, [1, 2, 3]);
+/// ```
+pub unsafe trait Arena {
+    /// Allocates memory for a value of `layout`.
+    ///
+    /// Implementations should panic or abort if the memory can not be allocated.
+    fn alloc_layout(&self, layout: Layout) -> NonNull<u8>;
+}
+
+#[cfg(feature = "bumpalo")]
+unsafe impl Arena for bumpalo::Bump {
+    #[inline]
+    fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        bumpalo::Bump::alloc_layout(self, layout)
+    }
+}
+
+/// A box that stores value inline or in a borrowed arena depending on its size
+///
+/// Create one with [`ArenaSmallBox::new_in`], or with `smallbox!(in arena, value)` to coerce the
+/// value to an unsized type. Dropping the box drops the value but never frees memory.
+pub struct ArenaSmallBox<'a, T: ?Sized, Space> {
+    space: MaybeUninit<UnsafeCell<Space>>,
+    ptr: NonNull<T>,
+    _phantom: PhantomData<(&'a (), T)>,
+}
+
+#[cfg(feature = "coerce")]
+impl<'a, T: ?Sized + Unsize<U>, U: ?Sized, Space> CoerceUnsized<ArenaSmallBox<'a, U, Space>>
+    for ArenaSmallBox<'a, T, Space>
+{
+}
+
+impl<'a, T: ?Sized, Space> ArenaSmallBox<'a, T, Space> {
+    /// Box value inline or in `arena` depending on its size.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "bumpalo")]
+    /// # {
+    /// use bumpalo::Bump;
+    /// use smallbox::arena::ArenaSmallBox;
+    /// use smallbox::space::S4;
+    ///
+    /// let arena = Bump::new();
+    /// let small: ArenaSmallBox<_, S4> = ArenaSmallBox::new_in([0usize; 2], &arena);
+    /// let large: ArenaSmallBox<_, S4> = ArenaSmallBox::new_in([1usize; 8], &arena);
+    ///
+    /// assert!(!small.is_arena());
+    /// assert!(large.is_arena());
+    /// # }
+    /// ```
+    #[inline(always)]
+    pub fn new_in<A>(val: T, arena: &'a A) -> ArenaSmallBox<'a, T, Space>
+    where
+        T: Sized,
+        A: ?Sized + Arena,
+    {
+        crate::smallbox!(in arena, val)
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub unsafe fn new_unchecked_in<U, A>(
+        val: U,
+        ptr: *const T,
+        arena: &'a A,
+    ) -> ArenaSmallBox<'a, T, Space>
+    where
+        U: Sized,
+        A: ?Sized + Arena,
+    {
+        let val = ManuallyDrop::new(val);
+        Self::new_copy::<U, A>(&val, ptr, arena)
+    }
+
+    unsafe fn new_copy<U, A>(
+        val: &U,
+        metadata_ptr: *const T,
+        arena: &'a A,
+    ) -> ArenaSmallBox<'a, T, Space>
+    where
+        U: ?Sized,
+        A: ?Sized + Arena,
+    {
+        let layout = Layout::for_value::<U>(val);
+        let space_layout = Layout::new::<Space>();
+
+        let mut space = MaybeUninit::<UnsafeCell<Space>>::uninit();
+
+        let (ptr_this, val_dst): (*mut u8, *mut u8) = if layout.size() <= space_layout.size()
+            && layout.align() <= space_layout.align()
+        {
+            // Stack.
+            #[cfg(feature = "stats")]
+            crate::stats::record_inline();
+            (INLINE_SENTINEL, space.as_mut_ptr().cast())
+        } else {
+            // Arena. The alignment is raised so that the address never equals
+            // `INLINE_SENTINEL`.
+            let layout =
+                Layout::from_size_align_unchecked(layout.size(), layout.align().max(MIN_ALIGNMENT));
+            let arena_ptr = arena.alloc_layout(layout).as_ptr();
+            (arena_ptr, arena_ptr)
+        };
+
+        // `self.ptr` always holds the metadata, even if stack allocated.
+        let ptr = sptr::with_metadata_of_mut(ptr_this, metadata_ptr);
+
+        ptr::copy_nonoverlapping(sptr::from_ref(val).cast(), val_dst, layout.size());
+
+        ArenaSmallBox {
+            space,
+            // Safety: ptr is either INLINE_SENTINEL or returned from the arena, which is never
+            // null.
+            ptr: NonNull::new_unchecked(ptr),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns true if data is allocated in the arena.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "bumpalo")]
+    /// # {
+    /// use bumpalo::Bump;
+    /// use smallbox::arena::ArenaSmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let arena = Bump::new();
+    ///
+    /// let stacked: ArenaSmallBox<usize, S1> = ArenaSmallBox::new_in(0usize, &arena);
+    /// assert!(!stacked.is_arena());
+    ///
+    /// let in_arena: ArenaSmallBox<(usize, usize), S1> = ArenaSmallBox::new_in((0, 1), &arena);
+    /// assert!(in_arena.is_arena());
+    /// # }
+    /// ```
+    #[inline]
+    pub fn is_arena(&self) -> bool {
+        self.ptr.as_ptr().cast::<u8>() != INLINE_SENTINEL
+    }
+
+    /// Consumes the ArenaSmallBox and returns ownership of the boxed value
+    ///
+    /// The arena memory of the value, if any, is not reclaimed until the arena is.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "bumpalo")]
+    /// # {
+    /// use bumpalo::Bump;
+    /// use smallbox::arena::ArenaSmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let arena = Bump::new();
+    /// let boxed: ArenaSmallBox<_, S1> = ArenaSmallBox::new_in(vec![21, 56, 420], &arena);
+    /// let val = boxed.into_inner();
+    /// assert_eq!(val[1], 56);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn into_inner(self) -> T
+    where T: Sized {
+        let this = ManuallyDrop::new(self);
+        unsafe {
+            let ret_val = this.as_ptr().read();
+            #[cfg(feature = "zeroize")]
+            this.wipe(mem::size_of::<T>());
+            ret_val
+        }
+    }
+
+    /// Overwrites the first `size` bytes of the value with zeros, after it has been moved out or
+    /// dropped.
+    #[cfg(feature = "zeroize")]
+    unsafe fn wipe(&self, size: usize) {
+        let ptr = if self.is_arena() {
+            self.ptr.as_ptr().cast()
+        } else {
+            UnsafeCell::raw_get(self.space.as_ptr()).cast()
+        };
+        crate::zeroize::wipe(ptr, size);
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *const T {
+        if self.is_arena() {
+            self.ptr.as_ptr()
+        } else {
+            sptr::with_metadata_of(self.space.as_ptr(), self.ptr.as_ptr())
+        }
+    }
+
+    #[inline]
+    fn as_mut_ptr(&mut self) -> *mut T {
+        if self.is_arena() {
+            self.ptr.as_ptr()
+        } else {
+            sptr::with_metadata_of_mut(self.space.as_mut_ptr(), self.ptr.as_ptr())
+        }
+    }
+}
+
+impl<'a, Space> ArenaSmallBox<'a, dyn Any, Space> {
+    /// Attempt to downcast the box to a concrete type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate smallbox;
+    ///
+    /// # fn main() {
+    /// # #[cfg(feature = "bumpalo")]
+    /// # {
+    /// use core::any::Any;
+    ///
+    /// use bumpalo::Bump;
+    /// use smallbox::arena::ArenaSmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let arena = Bump::new();
+    /// let value: ArenaSmallBox<dyn Any, S1> = smallbox!(in &arena, [7u64; 4]);
+    /// assert_eq!(*value.downcast::<[u64; 4]>().unwrap(), [7; 4]);
+    /// # }
+    /// # }
+    /// ```
+    #[inline]
+    pub fn downcast<T: Any>(self) -> Result<ArenaSmallBox<'a, T, Space>, Self> {
+        if self.is::<T>() {
+            unsafe { Ok(self.downcast_unchecked()) }
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<'a, Space> ArenaSmallBox<'a, dyn Any + Send, Space> {
+    /// Attempt to downcast the box to a concrete type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate smallbox;
+    ///
+    /// # fn main() {
+    /// # #[cfg(feature = "bumpalo")]
+    /// # {
+    /// use core::any::Any;
+    ///
+    /// use bumpalo::Bump;
+    /// use smallbox::arena::ArenaSmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let arena = Bump::new();
+    /// let value: ArenaSmallBox<dyn Any + Send, S1> = smallbox!(in &arena, 42u32);
+    /// assert_eq!(*value.downcast::<u32>().unwrap(), 42);
+    /// # }
+    /// # }
+    /// ```
+    #[inline]
+    pub fn downcast<T: Any>(self) -> Result<ArenaSmallBox<'a, T, Space>, Self> {
+        if self.is::<T>() {
+            unsafe { Ok(self.downcast_unchecked()) }
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<'a, T: ?Sized, Space> ArenaSmallBox<'a, T, Space> {
+    unsafe fn downcast_unchecked<U: Any>(self) -> ArenaSmallBox<'a, U, Space> {
+        let this = ManuallyDrop::new(self);
+
+        // The value stays where it is, only the metadata is dropped.
+        let downcasted = ArenaSmallBox {
+            space: ptr::read(&this.space),
+            ptr: this.ptr.cast(),
+            _phantom: PhantomData,
+        };
+        #[cfg(feature = "zeroize")]
+        if !this.is_arena() {
+            this.wipe(mem::size_of::<Space>());
+        }
+        downcasted
+    }
+}
+
+impl<'a, T: ?Sized, Space> ops::Deref for ArenaSmallBox<'a, T, Space> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<'a, T: ?Sized, Space> ops::DerefMut for ArenaSmallBox<'a, T, Space> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.as_mut_ptr() }
+    }
+}
+
+impl<'a, T: ?Sized, Space> ops::Drop for ArenaSmallBox<'a, T, Space> {
+    fn drop(&mut self) {
+        // The arena owns the memory, so only the value is dropped.
+        unsafe {
+            #[cfg(feature = "zeroize")]
+            let size = mem::size_of_val::<T>(&**self);
+            ptr::drop_in_place::<T>(self.as_mut_ptr());
+            #[cfg(feature = "zeroize")]
+            self.wipe(size);
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display, Space> fmt::Display for ArenaSmallBox<'a, T, Space> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug, Space> fmt::Debug for ArenaSmallBox<'a, T, Space> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized + PartialEq, Space> PartialEq for ArenaSmallBox<'a, T, Space> {
+    fn eq(&self, other: &ArenaSmallBox<'a, T, Space>) -> bool {
+        PartialEq::eq(&**self, &**other)
+    }
+}
+
+impl<'a, T: ?Sized + PartialOrd, Space> PartialOrd for ArenaSmallBox<'a, T, Space> {
+    fn partial_cmp(&self, other: &ArenaSmallBox<'a, T, Space>) -> Option<Ordering> {
+        PartialOrd::partial_cmp(&**self, &**other)
+    }
+}
+
+impl<'a, T: ?Sized + Ord, Space> Ord for ArenaSmallBox<'a, T, Space> {
+    fn cmp(&self, other: &ArenaSmallBox<'a, T, Space>) -> Ordering {
+        Ord::cmp(&**self, &**other)
+    }
+}
+
+impl<'a, T: ?Sized + Eq, Space> Eq for ArenaSmallBox<'a, T, Space> {}
+
+impl<'a, T: ?Sized + Hash, Space> Hash for ArenaSmallBox<'a, T, Space> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+unsafe impl<'a, T: ?Sized + Send, Space> Send for ArenaSmallBox<'a, T, Space> {}
+unsafe impl<'a, T: ?Sized + Sync, Space> Sync for ArenaSmallBox<'a, T, Space> {}
+
+#[cfg(test)]
+mod tests {
+    use core::any::Any;
+    use core::cell::Cell;
+    use core::cell::RefCell;
+    use core::ptr::NonNull;
+
+    use ::alloc::alloc::Layout;
+    use ::alloc::boxed::Box;
+    use ::alloc::vec;
+    use ::alloc::vec::Vec;
+
+    use super::Arena;
+    use super::ArenaSmallBox;
+    use crate::smallbox;
+    use crate::space::*;
+
+    /// An arena that hands out one leaked-until-drop chunk per allocation and counts them
+    #[derive(Default)]
+    struct TestArena {
+        chunks: RefCell<Vec<Vec<u64>>>,
+    }
+
+    unsafe impl Arena for TestArena {
+        fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+            assert!(layout.align() <= 8);
+            let mut chunk = vec![0u64; layout.size().div_ceil(8).max(1)];
+            let ptr = NonNull::new(chunk.as_mut_ptr().cast()).unwrap();
+            self.chunks.borrow_mut().push(chunk);
+            ptr
+        }
+    }
+
+    #[test]
+    fn test_arena() {
+        let arena = TestArena::default();
+
+        let stacked: ArenaSmallBox<[usize], S2> = smallbox!(in &arena, [1usize, 2]);
+        assert!(!stacked.is_arena());
+        assert_eq!(*stacked, [1, 2]);
+
+        let in_arena: ArenaSmallBox<[usize], S2> = smallbox!(in &arena, [1usize, 2, 3]);
+        assert!(in_arena.is_arena());
+        assert_eq!(*in_arena, [1, 2, 3]);
+        assert_eq!(arena.chunks.borrow().len(), 1);
+
+        // moving the box keeps the inline value reachable
+        let moved = Box::new(stacked);
+        assert_eq!(**moved, [1, 2]);
+
+        let mut closure: ArenaSmallBox<dyn FnMut() -> usize, S1> = smallbox!(in &arena, {
+            let mut n = 0;
+            let offset = [1usize; 4];
+            move || {
+                n += offset[0];
+                n
+            }
+        });
+        closure();
+        assert_eq!(closure(), 2);
+        assert_eq!(arena.chunks.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_drop() {
+        #[allow(dead_code)]
+        struct Struct<'a>(&'a Cell<usize>, [u8; 24]);
+        impl<'a> Drop for Struct<'a> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        trait Dummy {}
+        impl<'a> Dummy for Struct<'a> {}
+
+        let arena = TestArena::default();
+        let count = Cell::new(0);
+
+        let stacked: ArenaSmallBox<dyn Dummy, S4> = smallbox!(in &arena, Struct(&count, [0; 24]));
+        assert!(!stacked.is_arena());
+        let in_arena: ArenaSmallBox<dyn Dummy, S1> = smallbox!(in &arena, Struct(&count, [0; 24]));
+        assert!(in_arena.is_arena());
+
+        drop(stacked);
+        drop(in_arena);
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn test_downcast() {
+        let arena = TestArena::default();
+
+        let stacked: ArenaSmallBox<dyn Any, S1> = smallbox!(in &arena, 0x01u32);
+        assert_eq!(*stacked.downcast::<u32>().unwrap(), 0x01);
+
+        let in_arena: ArenaSmallBox<dyn Any + Send, S1> = smallbox!(in &arena, [1u64; 4]);
+        let in_arena = in_arena.downcast::<u8>().unwrap_err();
+        assert_eq!(*in_arena.downcast::<[u64; 4]>().unwrap(), [1; 4]);
+    }
+
+    #[test]
+    fn test_zst() {
+        #[repr(align(512))]
+        struct OveralignedZst;
+
+        /// An arena that only serves zero-sized allocations
+        struct ZstArena;
+
+        unsafe impl Arena for ZstArena {
+            fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+                assert_eq!(layout.size(), 0);
+                NonNull::new(crate::sptr::without_provenance_mut(layout.align())).unwrap()
+            }
+        }
+
+        let zst: ArenaSmallBox<[usize; 0], S1> = ArenaSmallBox::new_in([], &ZstArena);
+        assert!(!zst.is_arena());
+        assert_eq!(zst.len(), 0);
+
+        let zst: ArenaSmallBox<OveralignedZst, S1> =
+            ArenaSmallBox::new_in(OveralignedZst, &ZstArena);
+        assert!(zst.is_arena());
+        #[allow(clippy::as_conversions)]
+        let zst_addr = &*zst as *const OveralignedZst as usize;
+        assert_eq!(zst_addr % 512, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn test_zeroize() {
+        use core::mem;
+
+        use crate::zeroize::tests::wiped_by;
+
+        let arena = TestArena::default();
+
+        let stacked: ArenaSmallBox<[u64; 2], S2> = ArenaSmallBox::new_in([1, 2], &arena);
+        assert_eq!(wiped_by(|| assert_eq!(stacked.into_inner(), [1, 2])), 16);
+        let in_arena: ArenaSmallBox<[u64; 4], S2> = ArenaSmallBox::new_in([1; 4], &arena);
+        assert!(in_arena.is_arena());
+        assert_eq!(wiped_by(|| assert_eq!(in_arena.into_inner(), [1; 4])), 32);
+
+        let in_arena: ArenaSmallBox<[u64], S1> = smallbox!(in &arena, [1u64; 4]);
+        assert_eq!(wiped_by(|| drop(in_arena)), 32);
+
+        let stacked: ArenaSmallBox<dyn Any, S1> = smallbox!(in &arena, 1u32);
+        let mut downcasted = None;
+        assert_eq!(
+            wiped_by(|| downcasted = stacked.downcast::<u32>().ok()),
+            mem::size_of::<S1>()
+        );
+        assert_eq!(wiped_by(|| drop(downcasted)), 4);
+    }
+}
@@ -0,0 +1,217 @@
+//! Call-site audit of heap fallbacks
+//!
+//! This module is only available with the `audit` feature. Whenever a [`SmallBox`] allocates
+//! on the heap, the call site of [`SmallBox::new`], [`smallbox!`](crate::smallbox!),
+//! [`SmallBox::resize`] or [`SmallBox::into_box`] is recorded together with the type of the
+//! value and the size of the space. [`report`] then lists the call sites with the most heap
+//! allocations first, along with the smallest space that would have kept the value inline.
+//!
+//! # Example
+//!
+//! ```
+//! use smallbox::SmallBox;
+//! use smallbox::audit;
+//! use smallbox::space::S1;
+//!
+//! let heaped: SmallBox<[usize; 2], S1> = SmallBox::new([0, 1]);
+//!
+//! let report = audit::report();
+//! let entry = report
+//!     .entries
+//!     .iter()
+//!     .find(|entry| entry.type_name == "[usize; 2]")
+//!     .unwrap();
+//! assert_eq!(entry.suggested_space, Some("S2"));
+//! println!("{report}");
+//! # drop(heaped);
+//! ```
+//!
+//! [`SmallBox`]: crate::SmallBox
+//! [`SmallBox::new`]: crate::SmallBox::new
+//! [`SmallBox::resize`]: crate::SmallBox::resize
+//! [`SmallBox::into_box`]: crate::SmallBox::into_box
+
+use core::any::type_name;
+use core::fmt;
+use core::mem;
+use core::panic::Location;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::vec::Vec;
+
+use ::alloc::alloc::Layout;
+
+/// Identifies a call site, the type of the value and the size of the space
+type Key = (&'static str, u32, u32, &'static str, usize);
+
+static REGISTRY: Mutex<BTreeMap<Key, AuditEntry>> = Mutex::new(BTreeMap::new());
+
+/// The heap allocations made at one call site for one type and space
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// The call site that allocated
+    pub location: &'static Location<'static>,
+    /// The type name of the value, as returned by [`core::any::type_name`]
+    pub type_name: &'static str,
+    /// The size of the space in bytes
+    pub space_size: usize,
+    /// The size of the value in bytes
+    pub value_size: usize,
+    /// The alignment of the value in bytes
+    pub value_align: usize,
+    /// The number of heap allocations
+    pub heap_count: usize,
+    /// The smallest of the spaces in [`crate::space`] that would have kept the largest value
+    /// inline, if any
+    pub suggested_space: Option<&'static str>,
+}
+
+/// A report of all recorded heap allocations, sorted by descending heap count
+///
+/// The [`Display`](fmt::Display) implementation prints one call site per line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The recorded entries, sorted by descending heap count
+    pub entries: Vec<AuditEntry>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{}: {} heap allocation(s) of `{}` ({} bytes) in a {}-byte space, suggested space: {}",
+                entry.location,
+                entry.heap_count,
+                entry.type_name,
+                entry.value_size,
+                entry.space_size,
+                entry.suggested_space.unwrap_or("none"),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns all heap allocations recorded so far.
+pub fn report() -> Report {
+    let registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut entries: Vec<AuditEntry> = registry.values().cloned().collect();
+    entries.sort_by(|a, b| {
+        b.heap_count
+            .cmp(&a.heap_count)
+            .then_with(|| a.location.file().cmp(b.location.file()))
+            .then_with(|| a.location.line().cmp(&b.location.line()))
+            .then_with(|| a.location.column().cmp(&b.location.column()))
+    });
+    Report { entries }
+}
+
+/// Forgets all heap allocations recorded so far.
+pub fn reset() {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+/// Returns the smallest predefined space that fits a value of `layout`.
+fn suggest_space(layout: Layout) -> Option<&'static str> {
+    const SPACES: [(usize, &str); 7] = [
+        (1, "S1"),
+        (2, "S2"),
+        (4, "S4"),
+        (8, "S8"),
+        (16, "S16"),
+        (32, "S32"),
+        (64, "S64"),
+    ];
+
+    if layout.align() > mem::align_of::<usize>() {
+        return None;
+    }
+    SPACES
+        .iter()
+        .find(|(words, _)| words * mem::size_of::<usize>() >= layout.size())
+        .map(|(_, name)| *name)
+}
+
+/// Records a heap allocation for a value of type `U` and `layout` at the caller's location.
+#[track_caller]
+pub(crate) fn record<U: ?Sized, Space>(layout: Layout) {
+    if layout.size() == 0 {
+        return;
+    }
+
+    let location = Location::caller();
+    let type_name = type_name::<U>();
+    let space_size = mem::size_of::<Space>();
+    let key = (
+        location.file(),
+        location.line(),
+        location.column(),
+        type_name,
+        space_size,
+    );
+
+    let mut registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    let entry = registry.entry(key).or_insert_with(|| AuditEntry {
+        location,
+        type_name,
+        space_size,
+        value_size: 0,
+        value_align: 1,
+        heap_count: 0,
+        suggested_space: None,
+    });
+    entry.heap_count += 1;
+    entry.value_size = entry.value_size.max(layout.size());
+    entry.value_align = entry.value_align.max(layout.align());
+    entry.suggested_space = Layout::from_size_align(entry.value_size, entry.value_align)
+        .ok()
+        .and_then(suggest_space);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::any::Any;
+
+    use super::report;
+    use crate::SmallBox;
+    use crate::smallbox;
+    use crate::space::*;
+
+    #[test]
+    fn test_call_sites() {
+        let line = line!() + 2;
+        for _ in 0..3 {
+            let heaped: SmallBox<dyn Any, S1> = smallbox!([0u64; 3]);
+            drop(heaped);
+        }
+
+        let stacked: SmallBox<[u8; 16], S2> = SmallBox::new([0; 16]);
+        let resize_line = line!() + 1;
+        let resized: SmallBox<_, S1> = stacked.resize();
+        drop(resized);
+
+        let report = report();
+        let entry = report
+            .entries
+            .iter()
+            .find(|entry| entry.location.file() == file!() && entry.location.line() == line)
+            .unwrap();
+        assert_eq!(entry.heap_count, 3);
+        assert_eq!(entry.type_name, "[u64; 3]");
+        assert_eq!(entry.space_size, 8);
+        assert_eq!(entry.suggested_space, Some("S4"));
+
+        let entry = report
+            .entries
+            .iter()
+            .find(|entry| entry.location.file() == file!() && entry.location.line() == resize_line)
+            .unwrap();
+        assert_eq!(entry.heap_count, 1);
+        assert_eq!(entry.suggested_space, Some("S2"));
+    }
+}
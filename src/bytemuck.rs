@@ -0,0 +1,318 @@
+//! `bytemuck` support, enabled by the `bytemuck` feature
+//!
+//! Boxes of plain data can be created zeroed or from bytes with [`SmallBox::zeroed`] and
+//! [`SmallBox::from_bytes`], and viewed as bytes with [`SmallBox::as_bytes`]. Boxed slices are
+//! reinterpreted as slices of another element type with [`cast_slice_box`] without going through a
+//! `Vec` or a `Box`.
+//!
+//! A cast keeps an inline slice inline if the new element type is not more aligned than `Space`,
+//! and reuses a heap allocation if its layout does not change. Unlike `bytemuck`'s casts of
+//! `Box`, a cast between element types of different alignment does not fail, the bytes are copied
+//! into a new box instead.
+//!
+//! # Example
+//!
+//! ```
+//! use smallbox::SmallBox;
+//! use smallbox::bytemuck::cast_slice_box;
+//! use smallbox::space::S8;
+//!
+//! let packet: SmallBox<[u8], S8> = smallbox::smallbox!([0xff; 16]);
+//! let words: SmallBox<[u32], S8> = cast_slice_box(packet);
+//! assert!(!words.is_heap());
+//! assert_eq!(*words, [u32::MAX; 4]);
+//! ```
+
+use core::mem;
+use core::mem::ManuallyDrop;
+use core::ptr;
+
+use ::bytemuck::AnyBitPattern;
+use ::bytemuck::NoUninit;
+use ::bytemuck::Pod;
+use ::bytemuck::PodCastError;
+use ::bytemuck::Zeroable;
+
+use crate::SmallBox;
+use crate::policy::FallbackPolicy;
+
+impl<T, Space, P: FallbackPolicy> SmallBox<T, Space, P> {
+    /// Creates a box holding an all-zero value, without building the value on the stack first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let large: SmallBox<[u64; 512], S1> = SmallBox::zeroed();
+    /// assert!(large.is_heap());
+    /// assert!(large.iter().all(|&word| word == 0));
+    /// ```
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn zeroed() -> Self
+    where T: Zeroable {
+        unsafe { new_with_bytes(|dst| ptr::write_bytes(dst, 0, mem::size_of::<T>())) }
+    }
+
+    /// Creates a box holding a copy of the value in `bytes`, which need not be aligned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `bytes` is not the size of `T`. See
+    /// [`try_from_bytes`](SmallBox::try_from_bytes) for a non-panicking version.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let value: SmallBox<u32, S1> = SmallBox::from_bytes(&7u32.to_ne_bytes());
+    /// assert_eq!(*value, 7);
+    /// ```
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn from_bytes(bytes: &[u8]) -> Self
+    where T: AnyBitPattern {
+        match Self::try_from_bytes(bytes) {
+            Ok(this) => this,
+            Err(err) => panic!("from_bytes: {err}"),
+        }
+    }
+
+    /// Creates a box holding a copy of the value in `bytes`, which need not be aligned.
+    ///
+    /// Returns [`PodCastError::SizeMismatch`] if the length of `bytes` is not the size of `T`.
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, PodCastError>
+    where T: AnyBitPattern {
+        if bytes.len() != mem::size_of::<T>() {
+            return Err(PodCastError::SizeMismatch);
+        }
+        Ok(unsafe {
+            new_with_bytes(|dst| ptr::copy_nonoverlapping(bytes.as_ptr(), dst, bytes.len()))
+        })
+    }
+}
+
+impl<T, Space, P> SmallBox<T, Space, P> {
+    /// Returns the bytes of the boxed value.
+    ///
+    /// This is an associated function to avoid shadowing methods of `T`, so it has to be called as
+    /// `SmallBox::as_bytes(&b)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let value: SmallBox<u16, S1> = SmallBox::new(0x0102);
+    /// assert_eq!(SmallBox::as_bytes(&value), 0x0102u16.to_ne_bytes());
+    /// ```
+    #[inline]
+    pub fn as_bytes(this: &Self) -> &[u8]
+    where T: NoUninit {
+        ::bytemuck::bytes_of::<T>(this)
+    }
+
+    /// Returns the bytes of the boxed value, which can be modified in place.
+    ///
+    /// This is an associated function to avoid shadowing methods of `T`, so it has to be called as
+    /// `SmallBox::as_bytes_mut(&mut b)`.
+    #[inline]
+    pub fn as_bytes_mut(this: &mut Self) -> &mut [u8]
+    where T: Pod {
+        ::bytemuck::bytes_of_mut::<T>(this)
+    }
+}
+
+/// Creates a box for a `T` whose bytes are initialized by `init`.
+#[cfg_attr(feature = "audit", track_caller)]
+unsafe fn new_with_bytes<T, Space, P: FallbackPolicy>(
+    init: impl FnOnce(*mut u8),
+) -> SmallBox<T, Space, P> {
+    #[cfg(feature = "inline-only")]
+    crate::heap::assert_inline::<T, Space>();
+    let layout = core::alloc::Layout::new::<T>();
+    let mut this = SmallBox::<T, Space, P>::new_uninit(layout, ptr::null::<T>());
+    #[cfg(feature = "audit")]
+    if this.is_heap() {
+        crate::audit::record::<T, Space>(layout);
+    }
+    init(SmallBox::as_mut_ptr(&mut this).cast::<u8>());
+    ManuallyDrop::into_inner(this)
+}
+
+/// Reinterprets a boxed slice of `A` as a boxed slice of `B`.
+///
+/// # Panics
+///
+/// Panics if the bytes of the slice can not be split evenly into elements of `B`. See
+/// [`try_cast_slice_box`] for a non-panicking version.
+#[cfg_attr(feature = "audit", track_caller)]
+pub fn cast_slice_box<A, B, Space, P>(input: SmallBox<[A], Space, P>) -> SmallBox<[B], Space, P>
+where
+    A: NoUninit,
+    B: AnyBitPattern,
+    P: FallbackPolicy,
+{
+    match try_cast_slice_box(input) {
+        Ok(cast) => cast,
+        Err((err, _)) => panic!("cast_slice_box: {err}"),
+    }
+}
+
+/// Reinterprets a boxed slice of `A` as a boxed slice of `B`, returning the input on failure.
+///
+/// The slice keeps its length if `A` and `B` have the same size, otherwise the length is the
+/// number of bytes divided by the size of `B`. Fails with
+/// [`PodCastError::OutputSliceWouldHaveSlop`] if the bytes do not split evenly into elements of
+/// `B`.
+///
+/// # Example
+///
+/// ```
+/// use smallbox::SmallBox;
+/// use smallbox::bytemuck::try_cast_slice_box;
+/// use smallbox::space::S2;
+///
+/// let bytes: SmallBox<[u8], S2> = SmallBox::from_box(vec![0; 6].into());
+/// let (_, bytes) = try_cast_slice_box::<_, u32, _, _>(bytes).unwrap_err();
+///
+/// let halves: SmallBox<[u16], S2> = try_cast_slice_box(bytes).unwrap();
+/// assert_eq!(halves.len(), 3);
+/// ```
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "audit", track_caller)]
+pub fn try_cast_slice_box<A, B, Space, P>(
+    input: SmallBox<[A], Space, P>,
+) -> Result<SmallBox<[B], Space, P>, (PodCastError, SmallBox<[A], Space, P>)>
+where
+    A: NoUninit,
+    B: AnyBitPattern,
+    P: FallbackPolicy,
+{
+    let bytes = mem::size_of_val::<[A]>(&input);
+    let len = match mem::size_of::<B>() {
+        size if size == mem::size_of::<A>() => input.len(),
+        0 if bytes == 0 => 0,
+        size if size != 0 && bytes % size == 0 => bytes / size,
+        _ => return Err((PodCastError::OutputSliceWouldHaveSlop, input)),
+    };
+    Ok(unsafe { input.cast_bytes_unchecked(len) })
+}
+
+#[cfg(test)]
+mod tests {
+    use ::bytemuck::PodCastError;
+
+    use super::cast_slice_box;
+    use super::try_cast_slice_box;
+    use crate::SmallBox;
+    use crate::smallbox;
+    use crate::space::*;
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_zeroed() {
+        let small: SmallBox<[u32; 2], S1> = SmallBox::zeroed();
+        assert!(!small.is_heap());
+        assert_eq!(*small, [0, 0]);
+
+        let large: SmallBox<[u64; 64], S1> = SmallBox::zeroed();
+        assert!(large.is_heap());
+        assert!(large.iter().all(|&word| word == 0));
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_bytes() {
+        let mut value: SmallBox<[u16; 2], S1> = SmallBox::new([1, 2]);
+        SmallBox::as_bytes_mut(&mut value).copy_from_slice(&[0xff; 4]);
+        assert_eq!(*value, [u16::MAX; 2]);
+
+        // unaligned input
+        let bytes = [0u8, 1, 2, 3, 4, 5, 6, 7, 8];
+        let value: SmallBox<u64, S1> = SmallBox::from_bytes(&bytes[1..]);
+        assert_eq!(SmallBox::as_bytes(&value), &bytes[1..]);
+
+        let value: SmallBox<[u64; 4], S1> = SmallBox::try_from_bytes(&[7; 32]).unwrap();
+        assert!(value.is_heap());
+        assert_eq!(value[3], u64::from_ne_bytes([7; 8]));
+
+        assert_eq!(
+            SmallBox::<u32, S1>::try_from_bytes(&[0; 3]).err(),
+            Some(PodCastError::SizeMismatch)
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_cast_inline() {
+        let bytes: SmallBox<[u8], S2> = smallbox!([1u8, 0, 2, 0, 3, 0, 4, 0]);
+        let halves: SmallBox<[u16], S2> = cast_slice_box(bytes);
+        assert!(!halves.is_heap());
+        assert_eq!(*halves, [1, 2, 3, 4].map(u16::from_le));
+
+        let words: SmallBox<[u64], S2> = cast_slice_box(halves);
+        assert!(!words.is_heap());
+        assert_eq!(words.len(), 1);
+
+        // a `u128` is more aligned than `S2`, so the bytes are copied to the heap
+        let bytes: SmallBox<[u8], S2> = smallbox!([0u8; 16]);
+        let wide: SmallBox<[u128], S2> = cast_slice_box(bytes);
+        assert!(wide.is_heap());
+        assert_eq!(*wide, [0]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_cast_heap() {
+        let words: SmallBox<[u32], S1> = smallbox!([1u32, 2, 3, 4]);
+        assert!(words.is_heap());
+        let ptr = SmallBox::as_ptr(&words).cast::<u8>();
+
+        // same alignment, the allocation is reused
+        let floats: SmallBox<[f32], S1> = cast_slice_box(words);
+        assert_eq!(SmallBox::as_ptr(&floats).cast::<u8>(), ptr);
+        let words: SmallBox<[u32], S1> = cast_slice_box(floats);
+        assert_eq!(*words, [1, 2, 3, 4]);
+
+        // bytes and halves are both allocated with the minimum alignment
+        let bytes: SmallBox<[u8], S1> = smallbox!([0xffu8; 12]);
+        let ptr = SmallBox::as_ptr(&bytes).cast::<u8>();
+        let halves: SmallBox<[u16], S1> = cast_slice_box(bytes);
+        assert_eq!(SmallBox::as_ptr(&halves).cast::<u8>(), ptr);
+        assert_eq!(*halves, [u16::MAX; 6]);
+
+        // different alignment, the bytes are copied into a new allocation
+        let words: SmallBox<[u32], S1> = cast_slice_box(halves);
+        assert!(words.is_heap());
+        assert_eq!(*words, [u32::MAX; 3]);
+
+        // a reused allocation stays on the heap even if the slice would fit inline
+        let bytes: SmallBox<[u8], S1> = SmallBox::from_box([0u8; 4].into());
+        let halves: SmallBox<[u16], S1> = cast_slice_box(bytes);
+        assert!(halves.is_heap());
+
+        // a copy is stored inline if it fits
+        let words: SmallBox<[u32], S1> = cast_slice_box(halves);
+        assert!(!words.is_heap());
+    }
+
+    #[test]
+    fn test_cast_errors() {
+        let bytes: SmallBox<[u8], S1> = smallbox!([1u8, 2, 3]);
+        let (err, bytes) = try_cast_slice_box::<_, u16, _, _>(bytes).unwrap_err();
+        assert_eq!(err, PodCastError::OutputSliceWouldHaveSlop);
+        assert_eq!(*bytes, [1, 2, 3]);
+
+        let (err, _) = try_cast_slice_box::<_, (), _, _>(bytes).unwrap_err();
+        assert_eq!(err, PodCastError::OutputSliceWouldHaveSlop);
+
+        let empty: SmallBox<[u32], S1> = smallbox!([0u32; 0]);
+        let units: SmallBox<[()], S1> = cast_slice_box(empty);
+        assert!(units.is_empty());
+    }
+}
@@ -0,0 +1,440 @@
+use core::alloc::Layout;
+use core::cell::UnsafeCell;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::Hash;
+use core::hash::{self};
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::mem::MaybeUninit;
+use core::mem::{self};
+use core::ops;
+use core::ptr;
+use core::ptr::NonNull;
+
+use crate::heap;
+
+/// The storage of a [`CompactSmallBox`], which is either the inline space or the heap pointer
+///
+/// Which field is active is decided at compile time by [`CompactSmallBox::INLINE`]. The heap
+/// pointer is stored as unaligned bytes, so the storage is only as aligned as `Space`.
+union Storage<Space> {
+    inline: ManuallyDrop<MaybeUninit<UnsafeCell<Space>>>,
+    heap: [MaybeUninit<u8>; mem::size_of::<*mut u8>()],
+}
+
+/// A byte with a single valid value
+///
+/// A union has no invalid values, so this byte next to the [`Storage`] is the niche that keeps
+/// `Option<CompactSmallBox>` as large as `CompactSmallBox`.
+#[repr(u8)]
+enum Niche {
+    Valid,
+}
+
+/// A box for sized values that does not spend a pointer on inline values
+///
+/// For a sized `T`, whether a value fits in `Space` is known at compile time, so no runtime tag
+/// is needed to tell inline and heap values apart. `CompactSmallBox` overlaps the inline space
+/// with the heap pointer and adds a one-byte niche, so `Option<CompactSmallBox<T, Space>>` is as
+/// large as `CompactSmallBox<T, Space>`, like for [`SmallBox`].
+///
+/// # Size
+///
+/// The niche byte is padded to the alignment of `Space`, so `CompactSmallBox` is only smaller
+/// than a [`SmallBox`] with a space that is less aligned than a pointer:
+///
+/// - With [`S1`] to [`S8`], or any other pointer-aligned space, the box is `Space` plus a word,
+///   exactly as large as a [`SmallBox`]. `CompactSmallBox<u32, S1>` is 16 bytes on 64-bit targets.
+/// - With a space of `u8`, `u16` or `u32` arrays, the box is `Space` plus its alignment, so
+///   `CompactSmallBox<u32, [u32; 3]>` is 16 bytes where `SmallBox<u32, [u32; 3]>` is 24.
+///
+/// # Example
+///
+/// ```
+/// use core::mem::size_of;
+///
+/// use smallbox::CompactSmallBox;
+/// use smallbox::SmallBox;
+/// use smallbox::space::S1;
+///
+/// let compact: CompactSmallBox<u32, [u32; 3]> = CompactSmallBox::new(42);
+///
+/// assert!(!compact.is_heap());
+/// assert_eq!(*compact, 42);
+/// assert_eq!(size_of::<CompactSmallBox<u32, [u32; 3]>>(), 16);
+/// assert_eq!(size_of::<Option<CompactSmallBox<u32, [u32; 3]>>>(), 16);
+/// # #[cfg(target_pointer_width = "64")]
+/// assert_eq!(size_of::<SmallBox<u32, [u32; 3]>>(), 24);
+///
+/// // No gain with a pointer-aligned space
+/// assert_eq!(
+///     size_of::<CompactSmallBox<u32, S1>>(),
+///     size_of::<SmallBox<u32, S1>>()
+/// );
+/// ```
+///
+/// [`SmallBox`]: crate::SmallBox
+/// [`S1`]: crate::space::S1
+/// [`S8`]: crate::space::S8
+pub struct CompactSmallBox<T, Space> {
+    storage: Storage<Space>,
+    _niche: Niche,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Default, Space> Default for CompactSmallBox<T, Space> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T, Space> CompactSmallBox<T, Space> {
+    /// Whether values of `T` are stored in the inline space
+    const INLINE: bool = mem::size_of::<T>() <= mem::size_of::<Space>()
+        && mem::align_of::<T>() <= mem::align_of::<Space>();
+
+    /// Box value on stack or on heap depending on its size.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::CompactSmallBox;
+    /// use smallbox::space::*;
+    ///
+    /// let small: CompactSmallBox<_, S4> = CompactSmallBox::new([0usize; 2]);
+    /// assert_eq!(small.len(), 2);
+    ///
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
+    /// let large: CompactSmallBox<_, S4> = CompactSmallBox::new([1usize; 8]);
+    /// assert_eq!(large[7], 1);
+    ///
+    /// assert!(large.is_heap() == true);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn new(val: T) -> CompactSmallBox<T, Space> {
+        #[cfg(feature = "inline-only")]
+        heap::assert_inline::<T, Space>();
+        let storage = if Self::INLINE {
+            #[cfg(feature = "stats")]
+            crate::stats::record_inline();
+            let mut space = MaybeUninit::<UnsafeCell<Space>>::uninit();
+            unsafe { space.as_mut_ptr().cast::<T>().write(val) };
+            Storage {
+                inline: ManuallyDrop::new(space),
+            }
+        } else {
+            let heap_ptr = if mem::size_of::<T>() == 0 {
+                mem::forget(val);
+                NonNull::<T>::dangling().as_ptr()
+            } else {
+                #[cfg(feature = "stats")]
+                crate::stats::record_heap();
+                unsafe {
+                    let heap_ptr = heap::alloc(Layout::new::<T>()).cast::<T>();
+                    heap_ptr.write(val);
+                    heap_ptr
+                }
+            };
+            let mut storage = Storage {
+                heap: [MaybeUninit::uninit(); mem::size_of::<*mut u8>()],
+            };
+            unsafe {
+                ptr::addr_of_mut!(storage.heap)
+                    .cast::<*mut T>()
+                    .write_unaligned(heap_ptr)
+            };
+            storage
+        };
+
+        CompactSmallBox {
+            storage,
+            _niche: Niche::Valid,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns true if data is allocated on heap.
+    ///
+    /// This only depends on the types `T` and `Space`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::CompactSmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let stacked: CompactSmallBox<usize, S1> = CompactSmallBox::new(0usize);
+    /// assert!(!stacked.is_heap());
+    ///
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
+    /// let heaped: CompactSmallBox<(usize, usize), S1> = CompactSmallBox::new((0usize, 1usize));
+    /// assert!(heaped.is_heap());
+    /// # }
+    /// ```
+    #[inline]
+    pub const fn is_heap(&self) -> bool {
+        !Self::INLINE
+    }
+
+    /// Consumes the CompactSmallBox and returns ownership of the boxed value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::CompactSmallBox;
+    /// use smallbox::space::S4;
+    ///
+    /// let boxed: CompactSmallBox<_, S4> = CompactSmallBox::new(vec![21, 56, 420]);
+    /// let val = boxed.into_inner();
+    /// assert_eq!(val[1], 56);
+    /// ```
+    #[inline]
+    pub fn into_inner(self) -> T {
+        let this = ManuallyDrop::new(self);
+        unsafe {
+            let ret_val = this.as_ptr().read();
+            this.dealloc();
+            ret_val
+        }
+    }
+
+    /// Deallocates the heap memory without dropping the boxed value.
+    ///
+    /// With the `zeroize` feature, inline storage is wiped instead.
+    #[inline]
+    unsafe fn dealloc(&self) {
+        if !Self::INLINE && mem::size_of::<T>() != 0 {
+            heap::dealloc(self.heap_ptr().cast(), Layout::new::<T>());
+        }
+        #[cfg(feature = "zeroize")]
+        if Self::INLINE {
+            crate::zeroize::wipe(
+                UnsafeCell::raw_get(ptr::addr_of!(self.storage.inline).cast::<UnsafeCell<Space>>())
+                    .cast(),
+                mem::size_of::<Space>(),
+            );
+        }
+    }
+
+    /// Reads the heap pointer, which must be the active field of the storage.
+    #[inline]
+    unsafe fn heap_ptr(&self) -> *mut T {
+        ptr::addr_of!(self.storage.heap)
+            .cast::<*mut T>()
+            .read_unaligned()
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *const T {
+        if Self::INLINE {
+            ptr::addr_of!(self.storage.inline).cast()
+        } else {
+            unsafe { self.heap_ptr() }
+        }
+    }
+
+    #[inline]
+    fn as_mut_ptr(&mut self) -> *mut T {
+        if Self::INLINE {
+            ptr::addr_of_mut!(self.storage.inline).cast()
+        } else {
+            unsafe { self.heap_ptr() }
+        }
+    }
+}
+
+impl<T, Space> ops::Deref for CompactSmallBox<T, Space> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<T, Space> ops::DerefMut for CompactSmallBox<T, Space> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.as_mut_ptr() }
+    }
+}
+
+impl<T, Space> ops::Drop for CompactSmallBox<T, Space> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place::<T>(self.as_mut_ptr());
+            self.dealloc();
+        }
+    }
+}
+
+impl<T: Clone, Space> Clone for CompactSmallBox<T, Space> {
+    fn clone(&self) -> Self {
+        CompactSmallBox::new((**self).clone())
+    }
+}
+
+impl<T: fmt::Display, Space> fmt::Display for CompactSmallBox<T, Space> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Debug, Space> fmt::Debug for CompactSmallBox<T, Space> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: PartialEq, Space> PartialEq for CompactSmallBox<T, Space> {
+    fn eq(&self, other: &CompactSmallBox<T, Space>) -> bool {
+        PartialEq::eq(&**self, &**other)
+    }
+}
+
+impl<T: PartialOrd, Space> PartialOrd for CompactSmallBox<T, Space> {
+    fn partial_cmp(&self, other: &CompactSmallBox<T, Space>) -> Option<Ordering> {
+        PartialOrd::partial_cmp(&**self, &**other)
+    }
+}
+
+impl<T: Ord, Space> Ord for CompactSmallBox<T, Space> {
+    fn cmp(&self, other: &CompactSmallBox<T, Space>) -> Ordering {
+        Ord::cmp(&**self, &**other)
+    }
+}
+
+impl<T: Eq, Space> Eq for CompactSmallBox<T, Space> {}
+
+impl<T: Hash, Space> Hash for CompactSmallBox<T, Space> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+unsafe impl<T: Send, Space> Send for CompactSmallBox<T, Space> {}
+unsafe impl<T: Sync, Space> Sync for CompactSmallBox<T, Space> {}
+
+#[cfg(test)]
+mod tests {
+    use core::mem;
+
+    #[cfg(not(feature = "inline-only"))]
+    use ::alloc::vec;
+
+    use super::CompactSmallBox;
+    use crate::space::*;
+
+    #[test]
+    fn test_size() {
+        assert_eq!(
+            mem::size_of::<CompactSmallBox<u32, S1>>(),
+            2 * mem::size_of::<usize>()
+        );
+        assert_eq!(
+            mem::size_of::<CompactSmallBox<u32, S4>>(),
+            mem::size_of::<S4>() + mem::size_of::<usize>()
+        );
+        assert_eq!(mem::size_of::<CompactSmallBox<u32, [u32; 3]>>(), 16);
+        assert_eq!(
+            mem::size_of::<CompactSmallBox<[usize; 8], ()>>(),
+            mem::size_of::<usize>() + 1
+        );
+    }
+
+    #[test]
+    fn test_null_ptr_optimization() {
+        assert_eq!(
+            mem::size_of::<CompactSmallBox<u32, S1>>(),
+            mem::size_of::<Option<CompactSmallBox<u32, S1>>>()
+        );
+        assert_eq!(
+            mem::size_of::<CompactSmallBox<u32, [u32; 3]>>(),
+            mem::size_of::<Option<CompactSmallBox<u32, [u32; 3]>>>()
+        );
+        assert_eq!(
+            mem::size_of::<CompactSmallBox<[usize; 8], ()>>(),
+            mem::size_of::<Option<CompactSmallBox<[usize; 8], ()>>>()
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_compact() {
+        let stacked: CompactSmallBox<_, S1> = CompactSmallBox::new(1234usize);
+        assert!(!stacked.is_heap());
+        assert_eq!(*stacked, 1234);
+
+        let mut vec: CompactSmallBox<_, S4> = CompactSmallBox::new(vec![1, 2]);
+        assert!(!vec.is_heap());
+        vec.push(3);
+        assert_eq!(vec.clone().into_inner(), [1, 2, 3]);
+
+        let heaped: CompactSmallBox<_, S1> = CompactSmallBox::new((0usize, 1usize));
+        assert!(heaped.is_heap());
+        assert_eq!(*heaped, (0, 1));
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_drop() {
+        use core::cell::Cell;
+
+        struct Struct<'a>(&'a Cell<bool>, u8);
+        impl<'a> Drop for Struct<'a> {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        let flag = Cell::new(false);
+        let stacked: CompactSmallBox<_, S2> = CompactSmallBox::new(Struct(&flag, 0));
+        assert!(!stacked.is_heap());
+        drop(stacked);
+        assert!(flag.get());
+
+        let flag = Cell::new(false);
+        let heaped: CompactSmallBox<_, ()> = CompactSmallBox::new(Struct(&flag, 0));
+        assert!(heaped.is_heap());
+        assert_eq!(heaped.1, 0);
+        drop(heaped);
+        assert!(flag.get());
+    }
+
+    #[test]
+    fn test_overaligned_zst() {
+        #[repr(align(512))]
+        struct OveralignedZst;
+
+        let zst: CompactSmallBox<OveralignedZst, S1> = CompactSmallBox::new(OveralignedZst);
+        assert!(zst.is_heap());
+        #[allow(clippy::as_conversions)]
+        let zst_addr = &*zst as *const OveralignedZst as usize;
+        assert_eq!(zst_addr % 512, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn test_zeroize() {
+        use crate::zeroize::tests::wiped_by;
+
+        let stacked: CompactSmallBox<[u64; 2], S2> = CompactSmallBox::new([1, 2]);
+        assert_eq!(
+            wiped_by(|| assert_eq!(stacked.into_inner(), [1, 2])),
+            mem::size_of::<S2>()
+        );
+        let stacked: CompactSmallBox<[u64; 2], S2> = CompactSmallBox::new([1, 2]);
+        assert_eq!(wiped_by(|| drop(stacked)), mem::size_of::<S2>());
+
+        #[cfg(not(feature = "inline-only"))]
+        {
+            let heaped: CompactSmallBox<[u64; 4], S2> = CompactSmallBox::new([1; 4]);
+            assert!(heaped.is_heap());
+            assert_eq!(wiped_by(|| assert_eq!(heaped.into_inner(), [1; 4])), 32);
+            let heaped: CompactSmallBox<[u64; 4], S2> = CompactSmallBox::new([1; 4]);
+            assert_eq!(wiped_by(|| drop(heaped)), 32);
+        }
+    }
+}
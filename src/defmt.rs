@@ -0,0 +1,159 @@
+//! `defmt` support, enabled by the `defmt` feature
+//!
+//! [`SmallBox<T, Space>`] implements [`Format`] for any `T: ?Sized + Format` by formatting the
+//! value, the same way as `Box<T>` does.
+//!
+//! `Format` can not be used as a trait object, so a trait object type only implements it
+//! through [`defmt_dyn!`](crate::defmt_dyn!), for traits with [`DynFormat`] as a supertrait.
+//!
+//! # Example
+//!
+//! ```no_run
+//! #[macro_use]
+//! extern crate smallbox;
+//!
+//! # fn main() {
+//! use smallbox::SmallBox;
+//! use smallbox::defmt::DynFormat;
+//! use smallbox::space::S4;
+//!
+//! trait Sensor: DynFormat {
+//!     fn read(&mut self) -> u16;
+//! }
+//!
+//! #[derive(defmt::Format)]
+//! struct Thermometer {
+//!     channel: u8,
+//! }
+//!
+//! impl Sensor for Thermometer {
+//!     fn read(&mut self) -> u16 {
+//!         21
+//!     }
+//! }
+//!
+//! defmt_dyn!(dyn Sensor);
+//!
+//! fn log(sensor: &SmallBox<dyn Sensor, S4>) {
+//!     defmt::info!("sensor {}", sensor);
+//! }
+//!
+//! let sensor: SmallBox<dyn Sensor, S4> = smallbox!(Thermometer { channel: 2 });
+//! log(&sensor);
+//! # }
+//! ```
+//!
+//! [`SmallBox<T, Space>`]: crate::SmallBox
+
+#[doc(hidden)]
+pub use ::defmt as __defmt;
+use ::defmt::Format;
+use ::defmt::Formatter;
+
+use crate::SmallBox;
+
+impl<T: ?Sized + Format, Space, P> Format for SmallBox<T, Space, P> {
+    fn format(&self, fmt: Formatter) {
+        (**self).format(fmt)
+    }
+}
+
+/// A counterpart of [`Format`] that can be used as a trait object
+///
+/// Add it as a supertrait of traits used with [`defmt_dyn!`](crate::defmt_dyn!). It is
+/// implemented for every sized type that implements `Format`.
+pub trait DynFormat {
+    /// Writes the defmt representation of `self` to `fmt`.
+    fn format_dyn(&self, fmt: Formatter);
+}
+
+impl<T: Format> DynFormat for T {
+    #[inline]
+    fn format_dyn(&self, fmt: Formatter) {
+        self.format(fmt)
+    }
+}
+
+/// Implements `defmt::Format` for trait object types
+///
+/// The traits must have [`DynFormat`] as a supertrait. See the
+/// [module documentation](crate::defmt) for an example.
+///
+/// ```ignore
+/// defmt_dyn!(dyn Sensor, dyn Sensor + Send);
+/// ```
+#[macro_export]
+macro_rules! defmt_dyn {
+    ( $( $trait: ty ),+ $(,)? ) => {
+        $(
+            impl $crate::defmt::__defmt::Format for $trait {
+                fn format(&self, fmt: $crate::defmt::__defmt::Formatter) {
+                    $crate::defmt::DynFormat::format_dyn(self, fmt)
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use ::defmt::Format;
+
+    use super::DynFormat;
+    use crate::SmallBox;
+    use crate::space::*;
+
+    trait Sensor: DynFormat {
+        fn read(&self) -> u16;
+    }
+
+    #[derive(Format)]
+    struct Thermometer(u16);
+
+    impl Sensor for Thermometer {
+        fn read(&self) -> u16 {
+            self.0
+        }
+    }
+
+    defmt_dyn!(dyn Sensor, dyn Sensor + Send);
+
+    // Logging needs the defmt linker script to run, so the tests only check that the
+    // `Format` impls exist; the logger and panic handler just satisfy the linker.
+    #[::defmt::global_logger]
+    struct Logger;
+
+    unsafe impl ::defmt::Logger for Logger {
+        fn acquire() {}
+
+        unsafe fn flush() {}
+
+        unsafe fn release() {}
+
+        unsafe fn write(_: &[u8]) {}
+    }
+
+    ::defmt::timestamp!("");
+
+    #[::defmt::panic_handler]
+    fn panic() -> ! {
+        panic!()
+    }
+
+    fn assert_format<T: ?Sized + Format>(_: &T) {}
+
+    #[test]
+    fn test_format() {
+        assert_format(&SmallBox::<_, S1>::new(1u32));
+        assert_format::<SmallBox<[u8], S1>>(&crate::smallbox!([1u8, 2, 3]));
+        #[cfg(not(feature = "inline-only"))]
+        assert_format::<SmallBox<str, S1>>(&SmallBox::from_box("inline".into()));
+
+        let sensor: SmallBox<dyn Sensor, S1> = crate::smallbox!(Thermometer(21));
+        assert_format(&sensor);
+        assert_eq!(sensor.read(), 21);
+
+        let sensor: SmallBox<dyn Sensor + Send, S1> = crate::smallbox!(Thermometer(22));
+        assert_format(&sensor);
+    }
+}
@@ -0,0 +1,52 @@
+/// A custom dynamically sized type made of a header followed by a slice
+///
+/// Implementing this trait allows constructing the type in a [`SmallBox`] with
+/// [`SmallBox::from_header_and_slice`].
+///
+/// # Safety
+///
+/// The implementing type must be a `#[repr(C)]` struct with exactly two fields: a field of type
+/// `Header` followed by a field of type `[Element]`. `cast_slice_ptr` must return the given
+/// pointer unchanged except for its type, e.g. with an `as` cast.
+///
+/// # Example
+///
+/// ```
+/// use smallbox::HeaderSlice;
+/// use smallbox::SmallBox;
+/// use smallbox::space::S8;
+///
+/// #[repr(C)]
+/// struct Packet {
+///     header: u32,
+///     body: [u8],
+/// }
+///
+/// unsafe impl HeaderSlice for Packet {
+///     type Header = u32;
+///     type Element = u8;
+///
+///     fn cast_slice_ptr(ptr: *mut [u8]) -> *mut Packet {
+///         ptr as *mut Packet
+///     }
+/// }
+///
+/// let packet: SmallBox<Packet, S8> = SmallBox::from_header_and_slice(7, b"hello");
+/// assert!(!packet.is_heap());
+/// assert_eq!(packet.header, 7);
+/// assert_eq!(&packet.body, b"hello");
+/// ```
+///
+/// [`SmallBox`]: crate::SmallBox
+/// [`SmallBox::from_header_and_slice`]: crate::SmallBox::from_header_and_slice
+pub unsafe trait HeaderSlice {
+    /// The type of the leading sized field
+    type Header;
+
+    /// The element type of the trailing slice field
+    type Element;
+
+    /// Converts a pointer to the trailing slice into a pointer to `Self` with the same address and
+    /// length.
+    fn cast_slice_ptr(ptr: *mut [Self::Element]) -> *mut Self;
+}
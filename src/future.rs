@@ -0,0 +1,288 @@
+//! Type aliases and extension traits for boxed futures and streams
+//!
+//! [`SmallBoxFuture`] and [`LocalSmallBoxFuture`] are the counterparts of `futures`'
+//! `BoxFuture` and `LocalBoxFuture`, and [`FutureExt::small_boxed`] boxes a future like
+//! `FutureExt::boxed` does. A future that fits in `S` is stored inline, which makes returning a
+//! trait object from an async trait method allocation-free.
+//!
+//! A future that is too large for `S` falls back to the heap, or is passed to a
+//! [`FallbackPolicy`] with [`FutureExt::small_boxed_with`]. With the `audit` feature, the fallback
+//! is recorded with the type of the future and the call site of `small_boxed`, so
+//! `smallbox::audit::report()` lists the futures that need a larger space.
+//!
+//! # Example
+//!
+//! ```
+//! use smallbox::future::FutureExt;
+//! use smallbox::future::SmallBoxFuture;
+//! use smallbox::space::S8;
+//!
+//! trait Fetch {
+//!     fn fetch(&self, key: u32) -> SmallBoxFuture<'_, u32, S8>;
+//! }
+//!
+//! struct Doubler;
+//!
+//! impl Fetch for Doubler {
+//!     fn fetch(&self, key: u32) -> SmallBoxFuture<'_, u32, S8> {
+//!         async move { key * 2 }.small_boxed()
+//!     }
+//! }
+//!
+//! let fetched = Doubler.fetch(21);
+//! assert!(!fetched.is_heap());
+//! # assert_eq!(futures::executor::block_on(fetched), 42);
+//! ```
+
+use core::future::Future;
+
+#[cfg(feature = "futures")]
+use ::futures_core::Stream;
+
+use crate::SmallBox;
+use crate::policy::AllowHeap;
+use crate::policy::FallbackPolicy;
+
+/// A boxed future that can be sent between threads, stored inline if it fits in `S`
+pub type SmallBoxFuture<'a, T, S, P = AllowHeap> =
+    SmallBox<dyn Future<Output = T> + Send + 'a, S, P>;
+
+/// A boxed future that is not `Send`, stored inline if it fits in `S`
+pub type LocalSmallBoxFuture<'a, T, S, P = AllowHeap> = SmallBox<dyn Future<Output = T> + 'a, S, P>;
+
+/// Extension methods to box a future into a [`SmallBox`]
+///
+/// Implemented for all futures.
+pub trait FutureExt: Future {
+    /// Boxes the future as a [`SmallBoxFuture`], inline if it fits in `S`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::future::FutureExt;
+    /// use smallbox::space::S1;
+    /// use smallbox::space::S8;
+    ///
+    /// let small = async { 1 }.small_boxed::<S8>();
+    /// assert!(!small.is_heap());
+    ///
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
+    /// let buffer = [0u8; 64];
+    /// let large = async move { buffer.len() }.small_boxed::<S1>();
+    /// assert!(large.is_heap());
+    /// # }
+    /// ```
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed<'a, S>(self) -> SmallBoxFuture<'a, Self::Output, S>
+    where Self: Sized + Send + 'a {
+        crate::smallbox!(self)
+    }
+
+    /// Boxes the future as a [`SmallBoxFuture`] with the [`FallbackPolicy`] `P`, inline if it
+    /// fits in `S`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::future::FutureExt;
+    /// use smallbox::policy::PanicOnHeap;
+    /// use smallbox::space::S8;
+    ///
+    /// let small = async { 1 }.small_boxed_with::<S8, PanicOnHeap>();
+    /// assert!(!small.is_heap());
+    /// ```
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed_with<'a, S, P: FallbackPolicy>(self) -> SmallBoxFuture<'a, Self::Output, S, P>
+    where Self: Sized + Send + 'a {
+        crate::smallbox!(self)
+    }
+
+    /// Boxes the future as a [`LocalSmallBoxFuture`], inline if it fits in `S`.
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed_local<'a, S>(self) -> LocalSmallBoxFuture<'a, Self::Output, S>
+    where Self: Sized + 'a {
+        crate::smallbox!(self)
+    }
+
+    /// Boxes the future as a [`LocalSmallBoxFuture`] with the [`FallbackPolicy`] `P`, inline if
+    /// it fits in `S`.
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed_local_with<'a, S, P: FallbackPolicy>(
+        self,
+    ) -> LocalSmallBoxFuture<'a, Self::Output, S, P>
+    where Self: Sized + 'a {
+        crate::smallbox!(self)
+    }
+}
+
+impl<F: Future + ?Sized> FutureExt for F {}
+
+/// A boxed stream that can be sent between threads, stored inline if it fits in `S`
+#[cfg(feature = "futures")]
+pub type SmallBoxStream<'a, T, S, P = AllowHeap> = SmallBox<dyn Stream<Item = T> + Send + 'a, S, P>;
+
+/// A boxed stream that is not `Send`, stored inline if it fits in `S`
+#[cfg(feature = "futures")]
+pub type LocalSmallBoxStream<'a, T, S, P = AllowHeap> = SmallBox<dyn Stream<Item = T> + 'a, S, P>;
+
+/// Extension methods to box a stream into a [`SmallBox`]
+///
+/// Implemented for all streams. Only available with the `futures` feature.
+#[cfg(feature = "futures")]
+pub trait StreamExt: Stream {
+    /// Boxes the stream as a [`SmallBoxStream`], inline if it fits in `S`.
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed<'a, S>(self) -> SmallBoxStream<'a, Self::Item, S>
+    where Self: Sized + Send + 'a {
+        crate::smallbox!(self)
+    }
+
+    /// Boxes the stream as a [`SmallBoxStream`] with the [`FallbackPolicy`] `P`, inline if it
+    /// fits in `S`.
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed_with<'a, S, P: FallbackPolicy>(self) -> SmallBoxStream<'a, Self::Item, S, P>
+    where Self: Sized + Send + 'a {
+        crate::smallbox!(self)
+    }
+
+    /// Boxes the stream as a [`LocalSmallBoxStream`], inline if it fits in `S`.
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed_local<'a, S>(self) -> LocalSmallBoxStream<'a, Self::Item, S>
+    where Self: Sized + 'a {
+        crate::smallbox!(self)
+    }
+
+    /// Boxes the stream as a [`LocalSmallBoxStream`] with the [`FallbackPolicy`] `P`, inline if
+    /// it fits in `S`.
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed_local_with<'a, S, P: FallbackPolicy>(
+        self,
+    ) -> LocalSmallBoxStream<'a, Self::Item, S, P>
+    where Self: Sized + 'a {
+        crate::smallbox!(self)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<St: Stream + ?Sized> StreamExt for St {}
+
+#[cfg(test)]
+mod tests {
+    use ::alloc::rc::Rc;
+    use futures::executor::block_on;
+
+    use super::FutureExt;
+    use super::LocalSmallBoxFuture;
+    use super::SmallBoxFuture;
+    use crate::space::*;
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn test_small_boxed() {
+        let fut: SmallBoxFuture<'_, u32, S2> = async { 7 }.small_boxed();
+        assert!(!fut.is_heap());
+        assert_send(&fut);
+        assert_eq!(block_on(fut), 7);
+
+        let shared = Rc::new(3);
+        let fut: LocalSmallBoxFuture<'_, i32, S2> = async move { *shared }.small_boxed_local();
+        assert!(!fut.is_heap());
+        assert_eq!(block_on(fut), 3);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_heap() {
+        let buffer = [1u64; 16];
+        let fut = async move { buffer.iter().sum::<u64>() }.small_boxed::<S2>();
+        assert!(fut.is_heap());
+        assert_eq!(block_on(fut), 16);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_policy() {
+        use crate::policy::tests::CountFallbacks;
+        use crate::policy::tests::fallbacks_by;
+
+        let mut fut = None;
+        let fallbacks =
+            fallbacks_by(|| fut = Some(async { 1 }.small_boxed_with::<S2, CountFallbacks>()));
+        assert!(!fut.unwrap().is_heap());
+        assert_eq!(fallbacks, 0);
+
+        let buffer = [1u64; 16];
+        let mut fut = None;
+        let fallbacks = fallbacks_by(|| {
+            fut = Some::<LocalSmallBoxFuture<'_, _, S2, CountFallbacks>>(
+                async move { buffer.iter().sum::<u64>() }.small_boxed_local_with(),
+            )
+        });
+        let fut = fut.unwrap();
+        assert!(fut.is_heap());
+        assert_eq!(fallbacks, 1);
+        assert_eq!(block_on(fut), 16);
+    }
+
+    #[test]
+    #[cfg(all(not(feature = "inline-only"), debug_assertions))]
+    #[should_panic(expected = "does not fit in a space")]
+    fn test_debug_assert_inline() {
+        use crate::policy::DebugAssertInline;
+
+        let buffer = [0u8; 64];
+        drop(async move { buffer.len() }.small_boxed_with::<S1, DebugAssertInline>());
+    }
+
+    #[test]
+    fn test_borrowing() {
+        let values = [1, 2, 3];
+        let fut: SmallBoxFuture<'_, _, S2> = async { values.iter().sum::<i32>() }.small_boxed();
+        assert!(!fut.is_heap());
+        assert_eq!(block_on(fut), 6);
+    }
+
+    #[test]
+    #[cfg(feature = "audit")]
+    fn test_audit() {
+        let buffer = [0u8; 64];
+        let line = line!() + 1;
+        let fut = async move { buffer.len() }.small_boxed::<S1>();
+        assert!(fut.is_heap());
+
+        let report = crate::audit::report();
+        assert!(
+            report
+                .entries
+                .iter()
+                .any(|entry| entry.location.file() == file!() && entry.location.line() == line)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "futures")]
+    fn test_stream() {
+        use core::future::poll_fn;
+
+        use ::futures_core::Stream;
+
+        use super::StreamExt;
+
+        let stream = futures::stream::iter([1, 2]).small_boxed::<S4>();
+        assert!(!stream.is_heap());
+        assert_eq!(stream.size_hint(), (2, Some(2)));
+        let mut stream = core::pin::pin!(stream);
+        let next = block_on(poll_fn(|cx| stream.as_mut().poll_next(cx)));
+        assert_eq!(next, Some(1));
+    }
+}
@@ -0,0 +1,264 @@
+//! `futures` support, enabled by the `futures` and `futures-io` features
+//!
+//! [`SmallBox<T, Space>`] forwards `Stream` and `Sink` to the boxed value with the `futures`
+//! feature, and `AsyncRead`, `AsyncWrite`, `AsyncBufRead` and `AsyncSeek` with the `futures-io`
+//! feature. Like the `Future` impl, they project the pinned box to its pinned value, so `T` does
+//! not have to be `Unpin`.
+//!
+//! [`SmallBox<T, Space>`]: crate::SmallBox
+
+use core::pin::Pin;
+use core::task::Context;
+use core::task::Poll;
+#[cfg(feature = "futures-io")]
+use std::io;
+
+#[cfg(feature = "futures")]
+use ::futures_core::Stream;
+#[cfg(feature = "futures-io")]
+use ::futures_io::AsyncBufRead;
+#[cfg(feature = "futures-io")]
+use ::futures_io::AsyncRead;
+#[cfg(feature = "futures-io")]
+use ::futures_io::AsyncSeek;
+#[cfg(feature = "futures-io")]
+use ::futures_io::AsyncWrite;
+#[cfg(feature = "futures")]
+use ::futures_sink::Sink;
+
+use crate::SmallBox;
+
+#[cfg(feature = "futures")]
+impl<S: Stream + ?Sized, Space, P> Stream for SmallBox<S, Space, P> {
+    type Item = S::Item;
+
+    #[inline]
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().poll_next(cx)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (**self).size_hint()
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<S: Sink<Item> + ?Sized, Item, Space, P> Sink<Item> for SmallBox<S, Space, P> {
+    type Error = S::Error;
+
+    #[inline]
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().poll_ready(cx)
+    }
+
+    #[inline]
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        self.project().start_send(item)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().poll_close(cx)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<R: AsyncRead + ?Sized, Space, P> AsyncRead for SmallBox<R, Space, P> {
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().poll_read(cx, buf)
+    }
+
+    #[inline]
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [io::IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.project().poll_read_vectored(cx, bufs)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<W: AsyncWrite + ?Sized, Space, P> AsyncWrite for SmallBox<W, Space, P> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.project().poll_write_vectored(cx, bufs)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().poll_close(cx)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<B: AsyncBufRead + ?Sized, Space, P> AsyncBufRead for SmallBox<B, Space, P> {
+    #[inline]
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.project().poll_fill_buf(cx)
+    }
+
+    #[inline]
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.project().consume(amt)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<S: AsyncSeek + ?Sized, Space, P> AsyncSeek for SmallBox<S, Space, P> {
+    #[inline]
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: io::SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        self.project().poll_seek(cx, pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::poll_fn;
+    #[cfg(feature = "futures")]
+    use core::marker::PhantomPinned;
+    use core::pin::Pin;
+    #[cfg(feature = "futures")]
+    use core::task::Context;
+    #[cfg(feature = "futures")]
+    use core::task::Poll;
+
+    use futures::executor::block_on;
+
+    use crate::SmallBox;
+    use crate::smallbox;
+    use crate::space::*;
+
+    /// Yields the numbers below `end`, and is `!Unpin` to check that the impls project the pin.
+    #[cfg(feature = "futures")]
+    struct Count {
+        next: u32,
+        end: u32,
+        _pinned: PhantomPinned,
+    }
+
+    #[cfg(feature = "futures")]
+    impl ::futures_core::Stream for Count {
+        type Item = u32;
+
+        fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<u32>> {
+            let this = unsafe { self.get_unchecked_mut() };
+            if this.next == this.end {
+                return Poll::Ready(None);
+            }
+            this.next += 1;
+            Poll::Ready(Some(this.next - 1))
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            let len = usize::try_from(self.end - self.next).unwrap();
+            (len, Some(len))
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "futures")]
+    fn test_stream() {
+        use ::futures_core::Stream;
+
+        let count = Count {
+            next: 0,
+            end: 3,
+            _pinned: PhantomPinned,
+        };
+        let stream: SmallBox<dyn Stream<Item = u32>, S2> = smallbox!(count);
+        assert!(!stream.is_heap());
+        assert_eq!(stream.size_hint(), (3, Some(3)));
+
+        let mut stream = core::pin::pin!(stream);
+        let mut items = [0; 3];
+        for item in &mut items {
+            *item = block_on(poll_fn(|cx| stream.as_mut().poll_next(cx))).unwrap();
+        }
+        assert_eq!(items, [0, 1, 2]);
+        assert_eq!(block_on(poll_fn(|cx| stream.as_mut().poll_next(cx))), None);
+    }
+
+    #[test]
+    #[cfg(feature = "futures")]
+    fn test_sink() {
+        use ::alloc::vec::Vec;
+        use ::futures_sink::Sink;
+
+        let mut sink: SmallBox<Vec<u32>, S4> = SmallBox::new(Vec::new());
+        let mut pinned = Pin::new(&mut sink);
+        block_on(poll_fn(|cx| Sink::<u32>::poll_ready(pinned.as_mut(), cx))).unwrap();
+        pinned.as_mut().start_send(7).unwrap();
+        block_on(poll_fn(|cx| Sink::<u32>::poll_close(pinned.as_mut(), cx))).unwrap();
+        assert_eq!(*sink, [7]);
+    }
+
+    #[test]
+    #[cfg(feature = "futures-io")]
+    fn test_async_io() {
+        use std::io::SeekFrom;
+
+        use ::futures_io::AsyncBufRead;
+        use ::futures_io::AsyncRead;
+        use ::futures_io::AsyncSeek;
+        use ::futures_io::AsyncWrite;
+        use futures::io::Cursor;
+
+        let mut reader: SmallBox<dyn AsyncBufRead + Unpin, S4> = smallbox!(&b"abc"[..]);
+        assert!(!reader.is_heap());
+        let mut reader = Pin::new(&mut reader);
+        let filled = block_on(poll_fn(|cx| {
+            reader.as_mut().poll_fill_buf(cx).map_ok(|buf| buf.to_vec())
+        }));
+        assert_eq!(filled.unwrap(), b"abc");
+        reader.as_mut().consume(1);
+        let mut buf = [0; 2];
+        let read = block_on(poll_fn(|cx| reader.as_mut().poll_read(cx, &mut buf)));
+        assert_eq!((read.unwrap(), &buf), (2, b"bc"));
+
+        let mut writer: SmallBox<_, S4> = SmallBox::new(Cursor::new(Vec::new()));
+        let mut pinned = Pin::new(&mut writer);
+        let written = block_on(poll_fn(|cx| pinned.as_mut().poll_write(cx, b"xyz")));
+        assert_eq!(written.unwrap(), 3);
+        let pos = block_on(poll_fn(|cx| {
+            pinned.as_mut().poll_seek(cx, SeekFrom::Start(1))
+        }));
+        assert_eq!(pos.unwrap(), 1);
+        block_on(poll_fn(|cx| pinned.as_mut().poll_write(cx, b"Y"))).unwrap();
+        block_on(poll_fn(|cx| pinned.as_mut().poll_close(cx))).unwrap();
+        assert_eq!(writer.into_inner().into_inner(), b"xYz");
+    }
+}
@@ -0,0 +1,176 @@
+//! Heap allocation shared by all box types
+//!
+//! With the `inline-only` feature there is no heap. Constructors for a value whose size is known at
+//! compile time check with [`assert_inline`] that it fits, so a value that would need a heap
+//! fallback fails to compile. Storage whose size is only known at runtime, like the slot of a
+//! `StackBox`, panics instead.
+
+use core::alloc::Layout;
+#[cfg(feature = "inline-only")]
+use core::mem;
+#[cfg(not(feature = "inline-only"))]
+use core::ptr;
+
+#[cfg(not(feature = "inline-only"))]
+use ::alloc::alloc;
+#[cfg(not(feature = "inline-only"))]
+use ::alloc::alloc::handle_alloc_error;
+
+#[cfg(feature = "pool")]
+use crate::pool;
+#[cfg(not(feature = "inline-only"))]
+use crate::sptr;
+
+/// Allocates memory for `layout`, which must have a non-zero size
+///
+/// Never returns null, allocation failures are reported through `handle_alloc_error`.
+#[cfg(not(feature = "inline-only"))]
+#[inline]
+pub(crate) unsafe fn alloc(layout: Layout) -> *mut u8 {
+    #[cfg(feature = "stats")]
+    crate::stats::record_alloc(layout.size());
+
+    #[cfg(feature = "pool")]
+    if let Some(class) = pool::size_class(layout) {
+        return pool::alloc(class);
+    }
+
+    let ptr = alloc::alloc(layout);
+
+    if ptr.is_null() {
+        handle_alloc_error(layout)
+    }
+
+    ptr
+}
+
+/// Fails to compile if a `U` can not be stored in `Space` without a heap allocation
+///
+/// Zero-sized values never allocate, so they always pass.
+#[cfg(feature = "inline-only")]
+#[inline(always)]
+pub(crate) fn assert_inline<U, Space>() {
+    const {
+        assert!(
+            mem::size_of::<U>() == 0
+                || (mem::size_of::<U>() <= mem::size_of::<Space>()
+                    && mem::align_of::<U>() <= mem::align_of::<Space>()),
+            "the value does not fit in the space, and the `inline-only` feature disables the heap fallback"
+        )
+    }
+}
+
+/// Panics, a value of `layout` does not fit inline and there is no heap to fall back to
+#[cfg(feature = "inline-only")]
+#[cold]
+#[track_caller]
+pub(crate) unsafe fn alloc(layout: Layout) -> *mut u8 {
+    panic!(
+        "value of {} bytes (align {}) needs a heap fallback, which the `inline-only` feature disables",
+        layout.size(),
+        layout.align()
+    )
+}
+
+/// Deallocates memory returned by [`alloc`] with the same `layout`
+#[cfg(not(feature = "inline-only"))]
+#[inline]
+pub(crate) unsafe fn dealloc(ptr: *mut u8, layout: Layout) {
+    #[cfg(feature = "stats")]
+    crate::stats::record_dealloc(layout.size());
+
+    #[cfg(feature = "zeroize")]
+    crate::zeroize::wipe(ptr, layout.size());
+
+    #[cfg(feature = "pool")]
+    if let Some(class) = pool::size_class(layout) {
+        return pool::dealloc(ptr, class);
+    }
+
+    alloc::dealloc(ptr, layout)
+}
+
+/// Never called, since [`alloc`] never returns with the `inline-only` feature
+#[cfg(feature = "inline-only")]
+#[inline]
+pub(crate) unsafe fn dealloc(_: *mut u8, _: Layout) {
+    unreachable!()
+}
+
+/// Whether a value of `layout` has to be moved between a `Box` allocation and memory from
+/// [`alloc`] for the `aligned` layout
+///
+/// The allocations differ if `aligned` raised the alignment, or with the `pool` feature, if
+/// memory from [`alloc`] is a pooled block.
+#[cfg(not(feature = "inline-only"))]
+#[inline]
+fn needs_move(layout: Layout, aligned: Layout) -> bool {
+    #[cfg(feature = "pool")]
+    if pool::size_class(aligned).is_some() {
+        return true;
+    }
+    layout.align() != aligned.align()
+}
+
+/// Moves a value of `layout` out of a `Box` allocation into memory from [`alloc`] for the
+/// `aligned` layout, if the two differ
+///
+/// The `Box` allocation is freed if the value is moved. A zero-sized value gets a dangling
+/// pointer aligned to `aligned` instead, since the dangling pointer of a `Box` may be
+/// `INLINE_SENTINEL`.
+#[cfg(not(feature = "inline-only"))]
+#[inline]
+pub(crate) unsafe fn adopt(ptr: *mut u8, layout: Layout, aligned: Layout) -> *mut u8 {
+    if layout.size() == 0 {
+        return sptr::without_provenance_mut(aligned.align());
+    }
+    if !needs_move(layout, aligned) {
+        return ptr;
+    }
+
+    #[cfg(feature = "pool")]
+    let block = match pool::size_class(aligned) {
+        Some(class) => pool::alloc(class),
+        None => alloc::alloc(aligned),
+    };
+    #[cfg(not(feature = "pool"))]
+    let block = alloc::alloc(aligned);
+    if block.is_null() {
+        handle_alloc_error(aligned)
+    }
+
+    ptr::copy_nonoverlapping(ptr, block, layout.size());
+    #[cfg(feature = "zeroize")]
+    crate::zeroize::wipe(ptr, layout.size());
+    alloc::dealloc(ptr, layout);
+    block
+}
+
+/// Moves a value of `layout` out of memory from [`alloc`] for the `aligned` layout into a `Box`
+/// allocation, if the two differ
+///
+/// This is the inverse of [`adopt`]. The dangling pointer of a zero-sized value is kept, since it
+/// is aligned for the `Box` as well.
+#[cfg(not(feature = "inline-only"))]
+#[inline]
+pub(crate) unsafe fn release(ptr: *mut u8, layout: Layout, aligned: Layout) -> *mut u8 {
+    if layout.size() == 0 || !needs_move(layout, aligned) {
+        return ptr;
+    }
+
+    let boxed = alloc::alloc(layout);
+    if boxed.is_null() {
+        handle_alloc_error(layout)
+    }
+    ptr::copy_nonoverlapping(ptr, boxed, layout.size());
+    #[cfg(feature = "zeroize")]
+    crate::zeroize::wipe(ptr, layout.size());
+
+    #[cfg(feature = "pool")]
+    if let Some(class) = pool::size_class(aligned) {
+        pool::dealloc(ptr, class);
+        return boxed;
+    }
+    alloc::dealloc(ptr, aligned);
+    boxed
+}
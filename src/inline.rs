@@ -0,0 +1,183 @@
+//! Slices built element by element in an inline space
+//!
+//! Used by deserializers and generators that produce a `SmallBox<[T], Space>` without a source
+//! slice to copy.
+
+#[cfg(any(feature = "rkyv", feature = "arbitrary", feature = "proptest"))]
+use core::alloc::Layout;
+use core::cell::UnsafeCell;
+#[cfg(any(feature = "rkyv", feature = "arbitrary", feature = "proptest"))]
+use core::hint::unreachable_unchecked;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::mem::MaybeUninit;
+use core::mem::{self};
+use core::ptr;
+
+#[cfg(feature = "serde")]
+use ::alloc::vec::Vec;
+
+use crate::SmallBox;
+#[cfg(any(feature = "rkyv", feature = "arbitrary", feature = "proptest"))]
+use crate::heap;
+#[cfg(any(feature = "rkyv", feature = "arbitrary", feature = "proptest"))]
+use crate::policy::FallbackPolicy;
+#[cfg(any(feature = "rkyv", feature = "arbitrary", feature = "proptest"))]
+use crate::smallbox::MIN_ALIGNMENT;
+
+/// Elements of a slice being deserialized into an inline space
+///
+/// Drops the elements written so far if deserialization fails.
+pub(crate) struct InlineSlice<T, Space> {
+    space: MaybeUninit<UnsafeCell<Space>>,
+    pub(crate) len: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<T, Space> InlineSlice<T, Space> {
+    /// The number of elements that fit in `Space`
+    pub(crate) const CAPACITY: usize = if mem::align_of::<T>() > mem::align_of::<Space>() {
+        0
+    } else if mem::size_of::<T>() == 0 {
+        usize::MAX
+    } else {
+        mem::size_of::<Space>() / mem::size_of::<T>()
+    };
+
+    /// An upper bound for generated lengths, so that about half of them fit in `Space`
+    #[cfg(any(feature = "arbitrary", feature = "proptest"))]
+    pub(crate) const LEN_LIMIT: usize = if Self::CAPACITY == usize::MAX {
+        32
+    } else {
+        Self::CAPACITY * 2 + 1
+    };
+
+    pub(crate) fn new() -> Self {
+        InlineSlice {
+            space: MaybeUninit::uninit(),
+            len: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut T {
+        self.space.as_mut_ptr().cast()
+    }
+
+    /// Appends `elem`, which must not exceed the capacity.
+    pub(crate) fn push(&mut self, elem: T) {
+        debug_assert!(self.len < Self::CAPACITY);
+        unsafe { self.as_mut_ptr().add(self.len).write(elem) };
+        self.len += 1;
+    }
+
+    /// Moves the elements into a `Vec` with room for `additional` more elements.
+    #[cfg(feature = "serde")]
+    pub(crate) fn into_vec(self, additional: usize) -> Vec<T> {
+        let mut this = ManuallyDrop::new(self);
+        let mut vec = Vec::with_capacity(this.len.saturating_add(additional));
+        unsafe {
+            ptr::copy_nonoverlapping(this.as_mut_ptr(), vec.as_mut_ptr(), this.len);
+            vec.set_len(this.len);
+        }
+        vec
+    }
+
+    pub(crate) fn into_smallbox<P>(self) -> SmallBox<[T], Space, P> {
+        let this = ManuallyDrop::new(self);
+        let metadata_ptr = ptr::slice_from_raw_parts(ptr::null::<T>(), this.len);
+        unsafe { SmallBox::from_space(ptr::read(&this.space), metadata_ptr) }
+    }
+}
+
+impl<T, Space> Drop for InlineSlice<T, Space> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.as_mut_ptr(), self.len));
+        }
+    }
+}
+
+/// Builds a slice of `len` elements returned by `next`, inline if it fits in `Space`.
+///
+/// The slice is placed the same way as by `new_copy`, so an over-aligned empty slice still goes
+/// to the heap.
+#[cfg(any(feature = "rkyv", feature = "arbitrary", feature = "proptest"))]
+pub(crate) fn collect_slice<T, Space, P, E>(
+    len: usize,
+    mut next: impl FnMut() -> Result<T, E>,
+) -> Result<SmallBox<[T], Space, P>, E>
+where
+    P: FallbackPolicy,
+{
+    if len <= InlineSlice::<T, Space>::CAPACITY && mem::align_of::<T>() <= mem::align_of::<Space>()
+    {
+        let mut inline = InlineSlice::<T, Space>::new();
+        for _ in 0..len {
+            inline.push(next()?);
+        }
+        Ok(inline.into_smallbox())
+    } else {
+        collect_heap_slice(len, next)
+    }
+}
+
+/// Builds a slice of `len` elements returned by `next` in heap storage with the alignment of
+/// `SmallBox` heap values.
+#[cfg(any(feature = "rkyv", feature = "arbitrary", feature = "proptest"))]
+fn collect_heap_slice<T, Space, P, E>(
+    len: usize,
+    mut next: impl FnMut() -> Result<T, E>,
+) -> Result<SmallBox<[T], Space, P>, E>
+where
+    P: FallbackPolicy,
+{
+    /// Drops the elements written so far and frees the heap memory if `next` fails or panics.
+    struct Guard<T, Space, P> {
+        this: ManuallyDrop<SmallBox<[T], Space, P>>,
+        layout: Layout,
+        initialized: usize,
+    }
+
+    impl<T, Space, P> Drop for Guard<T, Space, P> {
+        fn drop(&mut self) {
+            unsafe {
+                let dst = SmallBox::as_mut_ptr(&mut self.this).cast::<T>();
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(dst, self.initialized));
+
+                let layout = self
+                    .layout
+                    .align_to(MIN_ALIGNMENT)
+                    .unwrap_or_else(|_| unreachable_unchecked());
+                if self.this.is_heap() && layout.size() != 0 {
+                    heap::dealloc(dst.cast(), layout);
+                }
+            }
+        }
+    }
+
+    let layout = Layout::array::<T>(len).expect("capacity overflow");
+    let metadata_ptr = ptr::slice_from_raw_parts(ptr::null::<T>(), len);
+
+    unsafe {
+        let mut guard = Guard {
+            this: SmallBox::new_uninit(layout, metadata_ptr),
+            layout,
+            initialized: 0,
+        };
+        #[cfg(feature = "audit")]
+        if guard.this.is_heap() {
+            crate::audit::record::<[T], Space>(layout);
+        }
+
+        let dst = SmallBox::as_mut_ptr(&mut guard.this).cast::<T>();
+        for i in 0..len {
+            dst.add(i).write(next()?);
+            guard.initialized += 1;
+        }
+
+        let this = ptr::read(&guard.this);
+        mem::forget(guard);
+        Ok(ManuallyDrop::into_inner(this))
+    }
+}
@@ -0,0 +1,143 @@
+//! Type aliases and an extension trait for boxed iterators
+//!
+//! [`IteratorExt::small_boxed`] erases the type of a long iterator chain into a
+//! [`SmallBoxIterator`], which is stored inline if the chain fits in `S`. The box implements
+//! [`Iterator`] itself, so it can be passed to anything taking `impl Iterator`.
+//!
+//! # Example
+//!
+//! ```
+//! use smallbox::iter::IteratorExt;
+//! use smallbox::iter::SmallBoxIterator;
+//! use smallbox::space::S4;
+//!
+//! fn evens(limit: u32, squared: bool) -> SmallBoxIterator<'static, u32, S4> {
+//!     let evens = (0..limit).filter(|n| n % 2 == 0);
+//!     if squared {
+//!         evens.map(|n| n * n).small_boxed()
+//!     } else {
+//!         evens.small_boxed()
+//!     }
+//! }
+//!
+//! let squares = evens(7, true);
+//! assert!(!squares.is_heap());
+//! assert_eq!(squares.collect::<Vec<_>>(), [0, 4, 16, 36]);
+//! ```
+
+use crate::SmallBox;
+use crate::policy::AllowHeap;
+use crate::policy::FallbackPolicy;
+
+/// A boxed iterator that can be sent between threads, stored inline if it fits in `S`
+pub type SmallBoxIterator<'a, T, S, P = AllowHeap> =
+    SmallBox<dyn Iterator<Item = T> + Send + 'a, S, P>;
+
+/// A boxed iterator that is not `Send`, stored inline if it fits in `S`
+pub type LocalSmallBoxIterator<'a, T, S, P = AllowHeap> =
+    SmallBox<dyn Iterator<Item = T> + 'a, S, P>;
+
+/// Extension methods to box an iterator into a [`SmallBox`]
+///
+/// Implemented for all iterators.
+pub trait IteratorExt: Iterator {
+    /// Boxes the iterator as a [`SmallBoxIterator`], inline if it fits in `S`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::iter::IteratorExt;
+    /// use smallbox::space::S2;
+    ///
+    /// let words = ["small", "box"];
+    /// let lengths = words.iter().map(|word| word.len()).small_boxed::<S2>();
+    /// assert!(!lengths.is_heap());
+    /// assert_eq!(lengths.sum::<usize>(), 8);
+    /// ```
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed<'a, S>(self) -> SmallBoxIterator<'a, Self::Item, S>
+    where Self: Sized + Send + 'a {
+        crate::smallbox!(self)
+    }
+
+    /// Boxes the iterator as a [`SmallBoxIterator`] with the [`FallbackPolicy`] `P`, inline if it
+    /// fits in `S`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::iter::IteratorExt;
+    /// use smallbox::policy::PanicOnHeap;
+    /// use smallbox::space::S2;
+    ///
+    /// let words = ["small", "box"];
+    /// let lengths = words
+    ///     .iter()
+    ///     .map(|word| word.len())
+    ///     .small_boxed_with::<S2, PanicOnHeap>();
+    /// assert!(!lengths.is_heap());
+    /// assert_eq!(lengths.sum::<usize>(), 8);
+    /// ```
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed_with<'a, S, P: FallbackPolicy>(self) -> SmallBoxIterator<'a, Self::Item, S, P>
+    where Self: Sized + Send + 'a {
+        crate::smallbox!(self)
+    }
+
+    /// Boxes the iterator as a [`LocalSmallBoxIterator`], inline if it fits in `S`.
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed_local<'a, S>(self) -> LocalSmallBoxIterator<'a, Self::Item, S>
+    where Self: Sized + 'a {
+        crate::smallbox!(self)
+    }
+
+    /// Boxes the iterator as a [`LocalSmallBoxIterator`] with the [`FallbackPolicy`] `P`, inline
+    /// if it fits in `S`.
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    fn small_boxed_local_with<'a, S, P: FallbackPolicy>(
+        self,
+    ) -> LocalSmallBoxIterator<'a, Self::Item, S, P>
+    where Self: Sized + 'a {
+        crate::smallbox!(self)
+    }
+}
+
+impl<I: Iterator + ?Sized> IteratorExt for I {}
+
+#[cfg(test)]
+mod tests {
+    use ::alloc::rc::Rc;
+
+    use super::IteratorExt;
+    use super::LocalSmallBoxIterator;
+    use super::SmallBoxIterator;
+    use crate::space::*;
+
+    #[test]
+    fn test_small_boxed() {
+        let values = [1u32, 2, 3, 4];
+        let chain: SmallBoxIterator<'_, u32, S2> = values.iter().map(|v| v * 10).small_boxed();
+        assert!(!chain.is_heap());
+        assert_eq!(chain.size_hint(), (4, Some(4)));
+        assert_eq!(chain.skip(1).step_by(2).sum::<u32>(), 60);
+
+        let shared = Rc::new(5);
+        let local: LocalSmallBoxIterator<'_, i32, S2> =
+            (0..3).map(move |n| n + *shared).small_boxed_local();
+        assert!(!local.is_heap());
+        assert_eq!(local.max(), Some(7));
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_heap() {
+        let buffer = [7u64; 16];
+        let owned = buffer.into_iter().small_boxed::<S2>();
+        assert!(owned.is_heap());
+        assert_eq!(owned.count(), 16);
+    }
+}
@@ -30,12 +30,15 @@
 //! let small: SmallBox<[u32; 2], S4> = SmallBox::new([1, 2]);
 //! assert!(!small.is_heap());
 //!
+//! # #[cfg(not(feature = "inline-only"))]
+//! # {
 //! // Large values automatically use heap allocation
 //! let large: SmallBox<[u32; 32], S4> = SmallBox::new([0; 32]);
 //! assert!(large.is_heap());
 //!
 //! // Use like a regular Box
 //! println!("Values: {:?} and length {}", *small, large.len());
+//! # }
 //! ```
 //!
 //! ## Configuration
@@ -44,12 +47,82 @@
 //!
 //! - **`std`** (enabled by default)
 //!   - Links to the standard library
+//!   - Forwards `std::io::{Read, Write, Seek, BufRead}` to the boxed value, like `Box` does
 //!   - Disable for `#![no_std]` environments: `default-features = false`
 //!
+//! - **`inline-only`** (optional)
+//!   - Removes the heap fallback and does not link the `alloc` crate
+//!   - A value that does not fit inline fails to compile, see [No-alloc Usage](#no-alloc-usage)
+//!   - Can not be combined with `serde`, `rkyv`, `arbitrary`, `proptest`, `pool` or `audit`
+//!
 //! - **`coerce`** (optional, requires nightly)
 //!   - Enables automatic coercion from `SmallBox<T>` to `SmallBox<dyn Trait>`
 //!   - Allows more ergonomic usage with trait objects
 //!
+//! - **`stats`** (optional)
+//!   - Counts inline and heap placements in global atomic counters
+//!   - Read them with `smallbox::stats::snapshot()`, works in `#![no_std]`
+//!
+//! - **`audit`** (optional, requires `std`)
+//!   - Records the call site, type and space of every heap fallback
+//!   - Read the sorted report with `smallbox::audit::report()`
+//!
+//! - **`bumpalo`** (optional)
+//!   - Implements [`arena::Arena`] for `bumpalo::Bump`
+//!
+//! - **`bytemuck`** (optional)
+//!   - Adds `zeroed`, `from_bytes` and `as_bytes` for boxes of plain data
+//!   - Casts boxed slices between plain data types with `smallbox::bytemuck::cast_slice_box`,
+//!     keeping inline storage or reusing the heap allocation when the layout allows
+//!
+//! - **`pool`** (optional, requires `std`)
+//!   - Serves heap fallbacks of up to 512 bytes from per-thread free lists of size-classed blocks
+//!   - Boxes can still be sent to and dropped on other threads
+//!   - `SmallBox::from_box` and `SmallBox::into_box` copy values that fit in a pooled block between
+//!     the `Box` allocation and the pool
+//!
+//! - **`rkyv`** (optional, requires Rust 1.81)
+//!   - Implements `Archive` and `Serialize` for `SmallBox<T: ?Sized, S>`, archived like a `Box<T>`
+//!     as a relative pointer to the value
+//!   - Implements `Deserialize` for sized `T`, `[T]` and `str`, choosing inline or heap storage the
+//!     same way as constructing a new box
+//!
+//! - **`serde`** (optional)
+//!   - Implements `Serialize` for `SmallBox<T: ?Sized + Serialize, S>`
+//!   - Implements `Deserialize` for sized `T`, `[T]` and `str`, writing slices and strings directly
+//!     into the inline space when they fit
+//!   - Serializes `SmallBox<dyn Trait, S>` through a registry of tagged concrete types, see
+//!     `smallbox::registry`
+//!
+//! - **`arbitrary`** (optional)
+//!   - Implements `Arbitrary` for `SmallBox<T, S>`, `SmallBox<[T], S>` and `SmallBox<str, S>`
+//!   - Generates both inline and heap-resident boxes, with slice lengths around the capacity of `S`
+//!
+//! - **`proptest`** (optional)
+//!   - Adds strategies in `smallbox::proptest` producing both inline and heap-resident boxes, and
+//!     implements `Arbitrary` for `SmallBox<T, S>` and `SmallBox<[T], S>`
+//!
+//! - **`defmt`** (optional)
+//!   - Implements `defmt::Format` for `SmallBox<T: ?Sized + Format, S>`
+//!   - Trait objects are supported through `smallbox::defmt::DynFormat` and `defmt_dyn!`
+//!
+//! - **`futures`** (optional)
+//!   - Implements `Stream` and `Sink` for `SmallBox<T: ?Sized, S>`, works in `#![no_std]`
+//!   - Adds `SmallBoxStream` aliases and `StreamExt::small_boxed` to [`future`]
+//!
+//! - **`futures-io`** (optional, requires `std`)
+//!   - Implements `AsyncRead`, `AsyncWrite`, `AsyncBufRead` and `AsyncSeek` from `futures-io`
+//!
+//! - **`tokio`** (optional, requires `std`)
+//!   - Implements tokio's `AsyncRead`, `AsyncWrite`, `AsyncBufRead` and `AsyncSeek`
+//!   - Like `Future`, the async traits do not require `T: Unpin`
+//!
+//! - **`zeroize`** (optional)
+//!   - Wipes inline storage and heap blocks with volatile writes when a value is dropped or moved
+//!     out by `into_inner`, `resize`, `into_box` or `downcast`
+//!   - Copies made by moving the box itself are not wiped, keep it in place or use a
+//!     [`StackBox`](stack::StackBox) for secrets
+//!
 //! ### No-std Usage
 //!
 //! SmallBox works in `#![no_std]` environments:
@@ -59,6 +132,28 @@
 //! smallbox = { version = "0.8", default-features = false }
 //! ```
 //!
+//! ### No-alloc Usage
+//!
+//! With the `inline-only` feature, SmallBox does not link the `alloc` crate and needs no global
+//! allocator. Values that fit in the space are stored inline as usual, including trait objects
+//! and slices:
+//!
+//! ```toml
+//! [dependencies]
+//! smallbox = { version = "0.8", default-features = false, features = ["inline-only"] }
+//! ```
+//!
+//! A value that would need a heap fallback fails to compile instead, at the call to
+//! [`SmallBox::new`], [`smallbox!`] or the other constructors, with the error "the value does
+//! not fit in the space". [`SmallBox::resize`] only compiles if the new space is at least as
+//! large and as aligned as the old one. Methods that always place the value on the heap, like
+//! [`SmallBox::pin`] and [`SmallBox::into_raw`], and conversions to and from `Box`, `Rc` and
+//! `Arc` are not available.
+//!
+//! Only sizes that are known at runtime are checked at runtime:
+//! [`SmallBox::from_header_and_slice`] and a [`StackBox`](stack::StackBox) whose slot is too
+//! small panic. An [`ArenaSmallBox`](arena::ArenaSmallBox) falls back to its arena instead.
+//!
 //! ### Custom Space Types
 //!
 //! Define custom capacities for specific needs:
@@ -75,7 +170,21 @@
 //! ```
 //!
 //! **Important**: Space alignment matters! If the space alignment is smaller than the value's
-//! required alignment, the value will be heap-allocated regardless of size.
+//! required alignment, the value will be heap-allocated regardless of size. Wrap the space in
+//! [`space::Align16`], [`space::Align32`] or [`space::Align64`] to store over-aligned values
+//! inline:
+//!
+//! ```rust
+//! use smallbox::SmallBox;
+//! use smallbox::space::Align32;
+//! use smallbox::space::S8;
+//!
+//! #[repr(align(32))]
+//! struct AlignedSimd([f32; 8]);
+//!
+//! let simd: SmallBox<_, Align32<S8>> = SmallBox::new(AlignedSimd([0.0; 8]));
+//! assert!(!simd.is_heap());
+//! ```
 //!
 //! ## Working with Unsized Types
 //!
@@ -148,6 +257,102 @@
 //! # }
 //! ```
 //!
+//! ### Pinning
+//!
+//! A value stored inline moves together with its [`SmallBox`], so [`SmallBox::pin`] and
+//! [`SmallBox::into_pin`] always place the value on the heap. For `T: Unpin`, `Pin::new` keeps
+//! the value inline.
+//!
+//! ```rust
+//! use std::pin::Pin;
+//!
+//! use smallbox::SmallBox;
+//! use smallbox::space::S4;
+//!
+//! # #[cfg(not(feature = "inline-only"))]
+//! # {
+//! let fut: Pin<SmallBox<_, S4>> = SmallBox::pin(async { 42 });
+//! assert_eq!(futures::executor::block_on(fut), 42);
+//! # }
+//! ```
+//!
+//! ### Boxed Futures
+//!
+//! [`SmallBoxFuture`](future::SmallBoxFuture) replaces `BoxFuture` in trait object returns, and
+//! [`FutureExt::small_boxed`](future::FutureExt::small_boxed) boxes a future on stable Rust.
+//!
+//! ```rust
+//! use smallbox::future::FutureExt;
+//! use smallbox::space::S4;
+//!
+//! let fut = async { 42 }.small_boxed::<S4>();
+//! assert!(!fut.is_heap());
+//! assert_eq!(futures::executor::block_on(fut), 42);
+//! ```
+//!
+//! ### Boxed Iterators
+//!
+//! [`SmallBox`] forwards [`Iterator`], [`DoubleEndedIterator`], [`ExactSizeIterator`] and
+//! [`FusedIterator`](core::iter::FusedIterator), and
+//! [`IteratorExt::small_boxed`](iter::IteratorExt::small_boxed) erases an iterator chain into
+//! inline storage.
+//!
+//! ```rust
+//! use smallbox::iter::IteratorExt;
+//! use smallbox::space::S2;
+//!
+//! let odds = (0..10).filter(|n| n % 2 == 1).small_boxed::<S2>();
+//! assert!(!odds.is_heap());
+//! assert_eq!(odds.sum::<i32>(), 25);
+//! ```
+//!
+//! ### Thin Handles
+//!
+//! [`ThinSmallBox`] stores the vtable or length of an unsized value next to the value instead of
+//! in the handle, which makes `ThinSmallBox<dyn Trait, S>` one word smaller than
+//! `SmallBox<dyn Trait, S>`. The vtable or length takes one word of the space, so
+//! `ThinSmallBox<dyn Trait, S2>` holds as much inline as `SmallBox<dyn Trait, S1>`. Use the
+//! [`thin_smallbox!`] macro to create one.
+//!
+//! ### Compact Sized Boxes
+//!
+//! For sized values, [`CompactSmallBox`] decides between inline and heap storage at compile time,
+//! and overlaps the inline space with the heap pointer. With a space that is less aligned than a
+//! pointer, `CompactSmallBox<u32, [u32; 3]>` is 16 bytes, while `SmallBox<u32, [u32; 3]>` is 24 on
+//! 64-bit targets. With a pointer-aligned space like `S1`, it is as large as a `SmallBox`.
+//!
+//! ### Heap Fallback Policies
+//!
+//! The optional third type parameter of [`SmallBox`] decides what happens when a value does not
+//! fit inline. The default [`AllowHeap`](policy::AllowHeap) silently allocates,
+//! [`PanicOnHeap`](policy::PanicOnHeap) panics and
+//! [`DebugAssertInline`](policy::DebugAssertInline) panics in debug builds only. Implement
+//! [`FallbackPolicy`](policy::FallbackPolicy) for custom behaviour.
+//!
+//! ```rust
+//! use smallbox::SmallBox;
+//! use smallbox::policy::PanicOnHeap;
+//! use smallbox::space::S4;
+//!
+//! type InlineOnly<T> = SmallBox<T, S4, PanicOnHeap>;
+//!
+//! let value: InlineOnly<[u32; 4]> = SmallBox::new([1, 2, 3, 4]);
+//! assert!(!value.is_heap());
+//! ```
+//!
+//! ### Arena Fallback
+//!
+//! [`ArenaSmallBox`](arena::ArenaSmallBox) takes its heap fallback from a borrowed
+//! [`Arena`](arena::Arena) and only drops the value on drop, leaving the memory to the arena.
+//! Create one with `smallbox!(in &arena, value)`.
+//!
+//! ### Borrowed Slots
+//!
+//! [`StackBox`](stack::StackBox) places the value in a caller-provided
+//! [`Slot`](stack::Slot), such as a `MaybeUninit<S4>` or a byte buffer, and only keeps a pointer
+//! to it, so large type-erased values can be passed around without moving their bytes. Use the
+//! [`stackbox!`] macro to coerce the value to an unsized type.
+//!
 //! ### Interoperability with `Box`
 //!
 //! Convert between [`SmallBox`] and [`Box`] when needed:
@@ -156,6 +361,8 @@
 //! use smallbox::SmallBox;
 //! use smallbox::space::S4;
 //!
+//! # #[cfg(not(feature = "inline-only"))]
+//! # {
 //! // Box -> SmallBox (data stays on heap)
 //! let boxed = Box::new([1, 2, 3, 4]);
 //! let small_box: SmallBox<_, S4> = SmallBox::from_box(boxed);
@@ -163,6 +370,7 @@
 //!
 //! // SmallBox -> Box (data moves to heap if needed)
 //! let back_to_box: Box<[i32; 4]> = SmallBox::into_box(small_box);
+//! # }
 //! ```
 
 #![cfg_attr(feature = "nightly", feature(strict_provenance, set_ptr_value))]
@@ -172,10 +380,74 @@
 #![deny(missing_docs)]
 #![deny(clippy::as_conversions)]
 
+// Tests use the `alloc` collections even when the library is built without heap fallbacks.
+#[cfg(any(not(feature = "inline-only"), test))]
 extern crate alloc;
 
+#[cfg(all(
+    feature = "inline-only",
+    any(
+        feature = "serde",
+        feature = "rkyv",
+        feature = "arbitrary",
+        feature = "proptest",
+        feature = "pool",
+        feature = "audit"
+    )
+))]
+compile_error!(
+    "the `inline-only` feature can not be combined with `serde`, `rkyv`, `arbitrary`, \
+     `proptest`, `pool` or `audit`, which need the heap fallback"
+);
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+pub mod arena;
+
+#[cfg(feature = "audit")]
+pub mod audit;
+#[cfg(feature = "bytemuck")]
+pub mod bytemuck;
+mod compact;
+#[cfg(feature = "defmt")]
+pub mod defmt;
+mod dst;
+pub mod future;
+#[cfg(any(feature = "futures", feature = "futures-io"))]
+mod futures;
+mod heap;
+#[cfg(any(
+    feature = "serde",
+    feature = "rkyv",
+    feature = "arbitrary",
+    feature = "proptest"
+))]
+mod inline;
+pub mod iter;
+pub mod policy;
+#[cfg(feature = "pool")]
+mod pool;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+#[cfg(feature = "serde")]
+pub mod registry;
+#[cfg(feature = "rkyv")]
+mod rkyv;
+#[cfg(feature = "serde")]
+mod serde;
 mod smallbox;
 pub mod space;
 mod sptr;
+pub mod stack;
+#[cfg(feature = "stats")]
+pub mod stats;
+mod thin;
+#[cfg(feature = "tokio")]
+mod tokio;
+#[cfg(feature = "zeroize")]
+mod zeroize;
 
+pub use crate::compact::CompactSmallBox;
+pub use crate::dst::HeaderSlice;
 pub use crate::smallbox::SmallBox;
+pub use crate::thin::ThinSmallBox;
@@ -0,0 +1,156 @@
+//! Policies that decide what happens when a value falls back to the heap
+//!
+//! The policy is the third type parameter of [`SmallBox`], so the behaviour is fixed per type
+//! alias:
+//!
+//! ```
+//! use smallbox::SmallBox;
+//! use smallbox::policy::PanicOnHeap;
+//! use smallbox::space::S4;
+//!
+//! type NoAllocBox<T> = SmallBox<T, S4, PanicOnHeap>;
+//!
+//! let small: NoAllocBox<[usize; 4]> = SmallBox::new([0; 4]);
+//! assert!(!small.is_heap());
+//! ```
+//!
+//! The policy is consulted by [`SmallBox::new`], [`smallbox!`](crate::smallbox!),
+//! [`SmallBox::resize`] and [`SmallBox::from_box`], for values that do not fit. Explicit requests
+//! for heap placement, such as [`SmallBox::into_box`] or [`SmallBox::pin`], are not checked.
+//!
+//! [`SmallBox`]: crate::SmallBox
+//! [`SmallBox::new`]: crate::SmallBox::new
+//! [`SmallBox::resize`]: crate::SmallBox::resize
+//! [`SmallBox::from_box`]: crate::SmallBox::from_box
+//! [`SmallBox::into_box`]: crate::SmallBox::into_box
+//! [`SmallBox::pin`]: crate::SmallBox::pin
+
+use core::alloc::Layout;
+
+/// Decides what happens when a value does not fit in the inline space
+///
+/// # Example
+///
+/// ```
+/// use std::alloc::Layout;
+/// use std::sync::atomic::AtomicUsize;
+/// use std::sync::atomic::Ordering;
+///
+/// use smallbox::SmallBox;
+/// use smallbox::policy::FallbackPolicy;
+/// use smallbox::space::S1;
+///
+/// static FALLBACKS: AtomicUsize = AtomicUsize::new(0);
+///
+/// struct CountFallbacks;
+///
+/// impl FallbackPolicy for CountFallbacks {
+///     fn on_heap_fallback(_value: Layout, _space: Layout) {
+///         FALLBACKS.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// # #[cfg(not(feature = "inline-only"))]
+/// # {
+/// let heaped: SmallBox<_, S1, CountFallbacks> = SmallBox::new([0usize; 2]);
+/// assert_eq!(FALLBACKS.load(Ordering::Relaxed), 1);
+/// # }
+/// ```
+pub trait FallbackPolicy {
+    /// Called before a value that does not fit in the inline space is placed on the heap.
+    ///
+    /// `value` is the layout of the value and `space` is the layout of the inline space. The
+    /// value is placed on the heap if this function returns.
+    fn on_heap_fallback(value: Layout, space: Layout);
+
+    /// Whether values may be placed on the heap under this policy.
+    ///
+    /// The generators of the `arbitrary` and `proptest` features only move values that fit
+    /// inline to the heap if this is `true`, and call [`on_heap_fallback`] when they do. Defaults
+    /// to `true`.
+    ///
+    /// [`on_heap_fallback`]: FallbackPolicy::on_heap_fallback
+    const ALLOWS_HEAP: bool = true;
+}
+
+/// Allows values to fall back to the heap silently
+///
+/// This is the default policy.
+pub struct AllowHeap;
+
+impl FallbackPolicy for AllowHeap {
+    #[inline(always)]
+    fn on_heap_fallback(_value: Layout, _space: Layout) {}
+}
+
+/// Panics when a value would fall back to the heap
+pub struct PanicOnHeap;
+
+impl FallbackPolicy for PanicOnHeap {
+    const ALLOWS_HEAP: bool = false;
+
+    #[cold]
+    #[track_caller]
+    fn on_heap_fallback(value: Layout, space: Layout) {
+        panic!(
+            "value of {} bytes (align {}) does not fit in a space of {} bytes (align {})",
+            value.size(),
+            value.align(),
+            space.size(),
+            space.align()
+        );
+    }
+}
+
+/// Panics in debug builds when a value would fall back to the heap, and allows it in release
+/// builds
+pub struct DebugAssertInline;
+
+impl FallbackPolicy for DebugAssertInline {
+    const ALLOWS_HEAP: bool = !cfg!(debug_assertions);
+
+    #[inline]
+    #[track_caller]
+    fn on_heap_fallback(value: Layout, space: Layout) {
+        debug_assert!(
+            false,
+            "value of {} bytes (align {}) does not fit in a space of {} bytes (align {})",
+            value.size(),
+            value.align(),
+            space.size(),
+            space.align()
+        );
+    }
+}
+
+#[cfg(all(test, not(feature = "inline-only")))]
+pub(crate) mod tests {
+    extern crate std;
+
+    use core::alloc::Layout;
+    use core::cell::Cell;
+
+    use super::FallbackPolicy;
+
+    std::thread_local! {
+        /// The number of heap fallbacks reported on the current thread
+        static FALLBACKS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// A policy that allows heap values and counts the fallbacks, see [`fallbacks_by`]
+    pub(crate) struct CountFallbacks;
+
+    impl FallbackPolicy for CountFallbacks {
+        fn on_heap_fallback(_value: Layout, _space: Layout) {
+            FALLBACKS.with(|fallbacks| fallbacks.set(fallbacks.get() + 1));
+        }
+    }
+
+    /// Returns the number of fallbacks reported to [`CountFallbacks`] on the current thread while
+    /// running `f`
+    pub(crate) fn fallbacks_by(f: impl FnOnce()) -> usize {
+        let before = FALLBACKS.with(Cell::get);
+        f();
+        FALLBACKS.with(Cell::get) - before
+    }
+}
@@ -0,0 +1,189 @@
+//! Per-thread free lists for small heap allocations
+//!
+//! Heap allocations of at most [`MAX_BLOCK_SIZE`] bytes are rounded up to a power-of-two size
+//! class and served from an intrusive free list of the current thread. Every block of a class is
+//! allocated from the global allocator with the same layout, so a block freed on another thread
+//! can be put on that thread's list, and blocks left on a list when the thread exits are
+//! returned to the global allocator.
+
+use core::cell::Cell;
+use core::ptr;
+
+use ::alloc::alloc;
+use ::alloc::alloc::Layout;
+use ::alloc::alloc::handle_alloc_error;
+
+/// The block sizes of the size classes
+const CLASS_SIZES: [usize; 6] = [16, 32, 64, 128, 256, 512];
+
+/// The size of the largest pooled block
+const MAX_BLOCK_SIZE: usize = CLASS_SIZES[CLASS_SIZES.len() - 1];
+
+/// The alignment of all pooled blocks
+const BLOCK_ALIGN: usize = 16;
+
+/// The number of free blocks kept per class and thread, further blocks are freed right away
+const MAX_CACHED: usize = 32;
+
+/// A stack of free blocks of one size class, linked through their first word
+struct FreeList {
+    head: Cell<*mut u8>,
+    len: Cell<usize>,
+}
+
+impl FreeList {
+    const fn new() -> Self {
+        FreeList {
+            head: Cell::new(ptr::null_mut()),
+            len: Cell::new(0),
+        }
+    }
+
+    fn pop(&self) -> Option<*mut u8> {
+        let head = self.head.get();
+        if head.is_null() {
+            return None;
+        }
+        // Safety: every block on the list stores the next block in its first word.
+        self.head.set(unsafe { head.cast::<*mut u8>().read() });
+        self.len.set(self.len.get() - 1);
+        Some(head)
+    }
+
+    /// Pushes `block` onto the list, or returns false if the list is full.
+    unsafe fn push(&self, block: *mut u8) -> bool {
+        if self.len.get() == MAX_CACHED {
+            return false;
+        }
+        block.cast::<*mut u8>().write(self.head.get());
+        self.head.set(block);
+        self.len.set(self.len.get() + 1);
+        true
+    }
+}
+
+struct Pool {
+    lists: [FreeList; CLASS_SIZES.len()],
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        for (class, list) in self.lists.iter().enumerate() {
+            while let Some(block) = list.pop() {
+                unsafe { alloc::dealloc(block, block_layout(class)) };
+            }
+        }
+    }
+}
+
+std::thread_local! {
+    static POOL: Pool = const {
+        Pool {
+            lists: [const { FreeList::new() }; CLASS_SIZES.len()],
+        }
+    };
+}
+
+/// Returns the size class of `layout`, if it is served from the pool.
+#[inline]
+pub(crate) fn size_class(layout: Layout) -> Option<usize> {
+    if layout.size() == 0 || layout.size() > MAX_BLOCK_SIZE || layout.align() > BLOCK_ALIGN {
+        return None;
+    }
+    CLASS_SIZES.iter().position(|&size| size >= layout.size())
+}
+
+#[inline]
+fn block_layout(class: usize) -> Layout {
+    // Safety: the class sizes are multiples of the power-of-two BLOCK_ALIGN.
+    unsafe { Layout::from_size_align_unchecked(CLASS_SIZES[class], BLOCK_ALIGN) }
+}
+
+/// Allocates a block of `class`, reusing a free block of the current thread if there is one
+#[inline]
+pub(crate) unsafe fn alloc(class: usize) -> *mut u8 {
+    if let Ok(Some(block)) = POOL.try_with(|pool| pool.lists[class].pop()) {
+        return block;
+    }
+
+    let layout = block_layout(class);
+    let block = alloc::alloc(layout);
+    if block.is_null() {
+        handle_alloc_error(layout)
+    }
+    block
+}
+
+/// Deallocates a block returned by [`alloc`] for `class`, on any thread
+#[inline]
+pub(crate) unsafe fn dealloc(block: *mut u8, class: usize) {
+    // The pool is gone while the thread is shutting down, then the block is freed directly.
+    let cached = POOL
+        .try_with(|pool| pool.lists[class].push(block))
+        .unwrap_or(false);
+    if !cached {
+        alloc::dealloc(block, block_layout(class));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use ::alloc::alloc::Layout;
+    use ::alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn test_size_class() {
+        assert_eq!(size_class(Layout::new::<()>()), None);
+        assert_eq!(size_class(Layout::new::<u8>()), Some(0));
+        assert_eq!(size_class(Layout::new::<[u8; 17]>()), Some(1));
+        assert_eq!(size_class(Layout::new::<[usize; 64]>()), Some(5));
+        assert_eq!(size_class(Layout::new::<[u8; 513]>()), None);
+        assert_eq!(size_class(Layout::from_size_align(16, 32).unwrap()), None);
+    }
+
+    #[test]
+    fn test_reuse() {
+        unsafe {
+            let block = alloc(2);
+            dealloc(block, 2);
+            assert_eq!(alloc(2), block);
+            dealloc(block, 2);
+        }
+    }
+
+    #[test]
+    fn test_bounded() {
+        unsafe {
+            let blocks: Vec<_> = (0..MAX_CACHED + 8).map(|_| alloc(3)).collect();
+            for block in blocks {
+                dealloc(block, 3);
+            }
+            POOL.with(|pool| assert_eq!(pool.lists[3].len.get(), MAX_CACHED));
+        }
+    }
+
+    #[test]
+    fn test_cross_thread() {
+        struct Block(*mut u8);
+        unsafe impl Send for Block {}
+        impl Block {
+            fn get(self) -> *mut u8 {
+                self.0
+            }
+        }
+
+        let block = Block(unsafe { alloc(4) });
+        thread::spawn(move || unsafe {
+            let block = block.get();
+            dealloc(block, 4);
+            assert_eq!(alloc(4), block);
+            dealloc(block, 4);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+//! `proptest` strategies, enabled by the `proptest` feature
+//!
+//! The strategies deliberately produce boxes stored both inline and on the heap. [`sized`] moves
+//! about half of the values to the heap even if they fit in `Space`, and [`slice()`] draws lengths
+//! from a range around the number of elements that fit in `Space`. `Arbitrary` is implemented for
+//! `SmallBox<T, Space>` and `SmallBox<[T], Space>` with these strategies.
+//!
+//! Values that fit are only moved to the heap if [`FallbackPolicy::ALLOWS_HEAP`] is `true`, and
+//! the move is reported to [`FallbackPolicy::on_heap_fallback`].
+//!
+//! # Example
+//!
+//! ```
+//! use proptest::prelude::*;
+//! use smallbox::SmallBox;
+//! use smallbox::space::S2;
+//!
+//! proptest! {
+//!     fn spills_past_capacity(values in any::<SmallBox<[u8], S2>>()) {
+//!         prop_assert_eq!(values.is_heap(), values.len() > 16);
+//!     }
+//! }
+//!
+//! spills_past_capacity();
+//! ```
+
+use core::convert::Infallible;
+
+use ::alloc::vec::Vec;
+use ::proptest::arbitrary::Arbitrary;
+use ::proptest::arbitrary::any_with;
+use ::proptest::bool;
+use ::proptest::collection;
+use ::proptest::collection::VecStrategy;
+use ::proptest::strategy::Map;
+use ::proptest::strategy::Strategy;
+
+use crate::SmallBox;
+use crate::inline::InlineSlice;
+use crate::inline::collect_slice;
+use crate::policy::FallbackPolicy;
+
+/// The strategy returned by [`sized`]
+pub type SizedStrategy<S, Space, P> = Map<
+    (S, bool::Any),
+    fn((<S as Strategy>::Value, bool)) -> SmallBox<<S as Strategy>::Value, Space, P>,
+>;
+
+/// The strategy returned by [`slice()`]
+pub type SliceStrategy<S, Space, P> = Map<
+    VecStrategy<S>,
+    fn(Vec<<S as Strategy>::Value>) -> SmallBox<[<S as Strategy>::Value], Space, P>,
+>;
+
+/// Returns a strategy for boxes of values from `value`, stored inline or on the heap.
+///
+/// Values that fit in `Space` are moved to the heap half of the time, if the policy `P` allows
+/// heap values.
+pub fn sized<S, Space, P>(value: S) -> SizedStrategy<S, Space, P>
+where
+    S: Strategy,
+    P: FallbackPolicy,
+{
+    (value, bool::ANY).prop_map(place)
+}
+
+/// Returns a strategy for boxed slices of elements from `element`, stored inline or on the heap.
+///
+/// Lengths range up to twice the number of elements that fit in `Space`, so about half of the
+/// slices are stored inline. Shrinking shortens the slice, moving it inline.
+pub fn slice<S, Space, P>(element: S) -> SliceStrategy<S, Space, P>
+where
+    S: Strategy,
+    P: FallbackPolicy,
+{
+    collection::vec(element, 0..=InlineSlice::<S::Value, Space>::LEN_LIMIT).prop_map(from_vec)
+}
+
+fn place<T, Space, P: FallbackPolicy>((value, heap): (T, bool)) -> SmallBox<T, Space, P> {
+    let this = SmallBox::new(value);
+    if heap {
+        this.into_heap_if_allowed()
+    } else {
+        this
+    }
+}
+
+fn from_vec<T, Space, P: FallbackPolicy>(vec: Vec<T>) -> SmallBox<[T], Space, P> {
+    let mut elems = vec.into_iter();
+    // `collect_slice` takes exactly `len` elements.
+    collect_slice::<_, _, _, Infallible>(elems.len(), || Ok(elems.next().unwrap()))
+        .unwrap_or_else(|never| match never {})
+}
+
+impl<T, Space, P> Arbitrary for SmallBox<T, Space, P>
+where
+    T: Arbitrary,
+    P: FallbackPolicy,
+{
+    type Parameters = T::Parameters;
+    type Strategy = SizedStrategy<T::Strategy, Space, P>;
+
+    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+        sized(any_with::<T>(args))
+    }
+}
+
+impl<T, Space, P> Arbitrary for SmallBox<[T], Space, P>
+where
+    T: Arbitrary,
+    P: FallbackPolicy,
+{
+    type Parameters = T::Parameters;
+    type Strategy = SliceStrategy<T::Strategy, Space, P>;
+
+    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+        slice(any_with::<T>(args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::proptest::prelude::*;
+    use ::proptest::strategy::ValueTree;
+    use ::proptest::test_runner::TestRunner;
+
+    use crate::SmallBox;
+    use crate::policy::AllowHeap;
+    use crate::space::*;
+
+    fn count_heap<T>(strategy: impl Strategy<Value = T>, is_heap: impl Fn(&T) -> bool) -> usize {
+        let mut runner = TestRunner::deterministic();
+        (0..200)
+            .filter(|_| is_heap(&strategy.new_tree(&mut runner).unwrap().current()))
+            .count()
+    }
+
+    #[test]
+    fn test_both_placements() {
+        let heap = count_heap(any::<SmallBox<[u8], S2>>(), |b| b.is_heap());
+        assert!(heap > 20 && heap < 180, "{heap}");
+
+        let heap = count_heap(any::<SmallBox<u32, S4>>(), |b| b.is_heap());
+        assert!(heap > 20 && heap < 180, "{heap}");
+
+        let heap = count_heap(any::<SmallBox<[u64; 8], S4>>(), |b| b.is_heap());
+        assert_eq!(heap, 200);
+    }
+
+    #[test]
+    fn test_shrink_inline() {
+        let mut runner = TestRunner::deterministic();
+        let mut tree = loop {
+            let tree = any::<SmallBox<[u8], S1>>().new_tree(&mut runner).unwrap();
+            if tree.current().is_heap() {
+                break tree;
+            }
+        };
+        while tree.simplify() {}
+        assert!(!tree.current().is_heap());
+    }
+
+    #[test]
+    fn test_policy() {
+        use crate::policy::PanicOnHeap;
+        use crate::policy::tests::CountFallbacks;
+        use crate::policy::tests::fallbacks_by;
+
+        let mut heap = 0;
+        let fallbacks = fallbacks_by(|| {
+            heap = count_heap(super::sized::<_, S4, CountFallbacks>(any::<u32>()), |b| {
+                b.is_heap()
+            })
+        });
+        assert!(heap > 20 && heap < 180, "{heap}");
+        assert_eq!(fallbacks, heap);
+
+        let heap = count_heap(super::sized::<_, S4, PanicOnHeap>(any::<u32>()), |b| {
+            b.is_heap()
+        });
+        assert_eq!(heap, 0);
+    }
+
+    proptest! {
+        #[test]
+        fn test_contents(values in super::slice::<_, S2, AllowHeap>(0..10u8)) {
+            prop_assert_eq!(values.is_heap(), values.len() > 16);
+            prop_assert!(values.iter().all(|&v| v < 10));
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! Serializing trait objects through a registry of concrete types
+//!
+//! A [`SmallBox<dyn Trait, Space>`] can not be deserialized on its own, because nothing says which
+//! concrete type to construct. [`type_registry!`](crate::type_registry!) assigns a stable tag to
+//! each concrete type of a trait, and values are serialized as a map with a single entry from the
+//! tag to the value, e.g. `{"position":{"x":1,"y":2}}`.
+//!
+//! The trait must have [`Registered`] as a supertrait, which is implemented for every `'static`
+//! type. Serializing works through the usual [`Serialize`] implementation, deserializing through
+//! the functions of this module with `#[serde(with = "smallbox::registry")]`. Unknown tags are
+//! reported as an unknown variant error.
+//!
+//! # Example
+//!
+//! ```
+//! #[macro_use]
+//! extern crate smallbox;
+//!
+//! # fn main() {
+//! use serde::Deserialize;
+//! use serde::Serialize;
+//! use smallbox::SmallBox;
+//! use smallbox::registry::Registered;
+//! use smallbox::space::S4;
+//!
+//! trait Component: Registered {
+//!     fn name(&self) -> String;
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Position {
+//!     x: i32,
+//!     y: i32,
+//! }
+//!
+//! impl Component for Position {
+//!     fn name(&self) -> String {
+//!         format!("position {} {}", self.x, self.y)
+//!     }
+//! }
+//!
+//! type_registry!(dyn Component {
+//!     "position" => Position,
+//! });
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Entity {
+//!     #[serde(with = "smallbox::registry")]
+//!     component: SmallBox<dyn Component, S4>,
+//! }
+//!
+//! let entity = Entity {
+//!     component: smallbox!(Position { x: 1, y: 2 }),
+//! };
+//! let json = serde_json::to_string(&entity).unwrap();
+//! assert_eq!(json, r#"{"component":{"position":{"x":1,"y":2}}}"#);
+//!
+//! let entity: Entity = serde_json::from_str(&json).unwrap();
+//! assert!(!entity.component.is_heap());
+//! assert_eq!(entity.component.name(), "position 1 2");
+//! # }
+//! ```
+//!
+//! [`SmallBox<dyn Trait, Space>`]: crate::SmallBox
+
+use core::any::Any;
+use core::fmt;
+use core::marker::PhantomData;
+
+#[doc(hidden)]
+pub use ::serde as __serde;
+use ::serde::Deserializer;
+use ::serde::Serialize;
+use ::serde::Serializer;
+use ::serde::de;
+use ::serde::ser::SerializeMap;
+
+use crate::SmallBox;
+use crate::policy::FallbackPolicy;
+
+/// Gives access to the concrete type behind a trait object
+///
+/// Add it as a supertrait of traits used with [`type_registry!`](crate::type_registry!). It is
+/// implemented for every `'static` type.
+pub trait Registered: Any {
+    /// Returns the value as [`Any`].
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> Registered for T {
+    #[inline]
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A trait object type whose concrete types have been registered with
+/// [`type_registry!`](crate::type_registry!)
+///
+/// This trait is implemented by the macro and not meant to be implemented by hand.
+pub trait TypeRegistry {
+    /// The tags of the registered types, in registration order
+    const TAGS: &'static [&'static str];
+
+    /// Serializes `self` as a map from its tag to its value.
+    fn serialize_tagged<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+
+    /// Deserializes the value of the map entry for `TAGS[tag]` into a box.
+    fn deserialize_tagged<'de, A, Space, P>(
+        tag: usize,
+        map: &mut A,
+    ) -> Result<SmallBox<Self, Space, P>, A::Error>
+    where
+        A: de::MapAccess<'de>,
+        P: FallbackPolicy;
+}
+
+/// Registers the concrete types of a trait object type for serialization
+///
+/// Implements [`TypeRegistry`] and `Serialize` for the trait object type. The trait must have
+/// [`Registered`] as a supertrait, and every registered type must implement `Serialize`,
+/// `Deserialize` and the trait. See the [module documentation](crate::registry) for an example.
+///
+/// ```ignore
+/// type_registry!(dyn Component {
+///     "position" => Position,
+///     "velocity" => Velocity,
+/// });
+/// ```
+#[macro_export]
+macro_rules! type_registry {
+    ( $trait: ty { $( $tag: literal => $ty: ty ),* $(,)? } ) => {
+        impl $crate::registry::TypeRegistry for $trait {
+            const TAGS: &'static [&'static str] = &[ $( $tag ),* ];
+
+            fn serialize_tagged<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                S: $crate::registry::__serde::Serializer,
+            {
+                let any = $crate::registry::Registered::as_any(self);
+                $(
+                    if let ::core::option::Option::Some(value) = any.downcast_ref::<$ty>() {
+                        return $crate::registry::serialize_entry(serializer, $tag, value);
+                    }
+                )*
+                ::core::result::Result::Err(<S::Error as $crate::registry::__serde::ser::Error>::custom(
+                    "the type of the value is not registered with `type_registry!`",
+                ))
+            }
+
+            fn deserialize_tagged<'de, A, Space, P>(
+                tag: usize,
+                map: &mut A,
+            ) -> ::core::result::Result<$crate::SmallBox<Self, Space, P>, A::Error>
+            where
+                A: $crate::registry::__serde::de::MapAccess<'de>,
+                P: $crate::policy::FallbackPolicy,
+            {
+                let tag = <Self as $crate::registry::TypeRegistry>::TAGS[tag];
+                $(
+                    if tag == $tag {
+                        let value: $ty = map.next_value()?;
+                        return ::core::result::Result::Ok($crate::smallbox!(value));
+                    }
+                )*
+                ::core::unreachable!()
+            }
+        }
+
+        impl $crate::registry::__serde::Serialize for $trait {
+            fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                S: $crate::registry::__serde::Serializer,
+            {
+                $crate::registry::TypeRegistry::serialize_tagged(self, serializer)
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+pub fn serialize_entry<S: Serializer, V: Serialize>(
+    serializer: S,
+    tag: &'static str,
+    value: &V,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(tag, value)?;
+    map.end()
+}
+
+/// Serializes a box of a registered trait object type, for `#[serde(with = "smallbox::registry")]`
+pub fn serialize<T, Space, P, S>(
+    value: &SmallBox<T, Space, P>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + TypeRegistry,
+    S: Serializer,
+{
+    (**value).serialize_tagged(serializer)
+}
+
+/// Deserializes a box of a registered trait object type, for
+/// `#[serde(with = "smallbox::registry")]`
+///
+/// The value is stored inline if it fits in `Space`.
+pub fn deserialize<'de, T, Space, P, D>(deserializer: D) -> Result<SmallBox<T, Space, P>, D::Error>
+where
+    T: ?Sized + TypeRegistry,
+    P: FallbackPolicy,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_map(RegistryVisitor(PhantomData, PhantomData))
+}
+
+struct RegistryVisitor<T: ?Sized, Space, P>(PhantomData<(Space, P)>, PhantomData<T>);
+
+impl<'de, T, Space, P> de::Visitor<'de> for RegistryVisitor<T, Space, P>
+where
+    T: ?Sized + TypeRegistry,
+    P: FallbackPolicy,
+{
+    type Value = SmallBox<T, Space, P>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map with a single entry from a type tag to a value")
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let Some(tag) = map.next_key_seed(TagSeed(T::TAGS))? else {
+            return Err(de::Error::invalid_length(0, &self));
+        };
+        let value = T::deserialize_tagged(tag, &mut map)?;
+        if map.next_key::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(2, &self));
+        }
+        Ok(value)
+    }
+}
+
+/// Deserializes a tag into its index in the registered tags
+struct TagSeed(&'static [&'static str]);
+
+impl<'de> de::DeserializeSeed<'de> for TagSeed {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<usize, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> de::Visitor<'de> for TagSeed {
+    type Value = usize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a type tag")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<usize, E> {
+        self.0
+            .iter()
+            .position(|tag| *tag == v)
+            .ok_or_else(|| E::unknown_variant(v, self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::alloc::string::ToString;
+    use ::alloc::vec::Vec;
+    use ::serde::Deserialize;
+    use ::serde::Serialize;
+
+    use super::Registered;
+    use crate::SmallBox;
+    use crate::smallbox;
+    use crate::space::*;
+
+    trait Shape: Registered {
+        fn area(&self) -> f64;
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Square(f64);
+
+    impl Shape for Square {
+        fn area(&self) -> f64 {
+            self.0 * self.0
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Polygon {
+        points: Vec<(f64, f64)>,
+        area: f64,
+    }
+
+    impl Shape for Polygon {
+        fn area(&self) -> f64 {
+            self.area
+        }
+    }
+
+    struct Unregistered;
+
+    impl Shape for Unregistered {
+        fn area(&self) -> f64 {
+            0.0
+        }
+    }
+
+    type_registry!(dyn Shape {
+        "square" => Square,
+        "polygon" => Polygon,
+    });
+
+    #[derive(Serialize, Deserialize)]
+    struct Scene {
+        #[serde(with = "crate::registry")]
+        shape: SmallBox<dyn Shape, S2>,
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let scene = Scene {
+            shape: smallbox!(Square(2.0)),
+        };
+        let json = serde_json::to_string(&scene).unwrap();
+        assert_eq!(json, r#"{"shape":{"square":2.0}}"#);
+
+        let scene: Scene = serde_json::from_str(&json).unwrap();
+        assert!(!scene.shape.is_heap());
+        assert_eq!(scene.shape.area(), 4.0);
+
+        let polygon = Polygon {
+            points: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)].to_vec(),
+            area: 0.5,
+        };
+        let scene = Scene {
+            shape: smallbox!(polygon),
+        };
+        let json = serde_json::to_string(&scene).unwrap();
+        assert_eq!(
+            json,
+            r#"{"shape":{"polygon":{"points":[[0.0,0.0],[1.0,0.0],[0.0,1.0]],"area":0.5}}}"#
+        );
+
+        let scene: Scene = serde_json::from_str(&json).unwrap();
+        assert!(scene.shape.is_heap());
+        assert_eq!(scene.shape.area(), 0.5);
+    }
+
+    #[test]
+    fn test_errors() {
+        let unknown = serde_json::from_str::<Scene>(r#"{"shape":{"circle":1.0}}"#);
+        let message = unknown.err().unwrap().to_string();
+        assert!(message.contains("unknown variant `circle`"), "{message}");
+        assert!(
+            message.contains("expected `square` or `polygon`"),
+            "{message}"
+        );
+
+        let empty = serde_json::from_str::<Scene>(r#"{"shape":{}}"#);
+        assert!(empty.is_err());
+
+        let extra = serde_json::from_str::<Scene>(r#"{"shape":{"square":1.0,"square":2.0}}"#);
+        assert!(extra.is_err());
+
+        let unregistered: SmallBox<dyn Shape, S2> = smallbox!(Unregistered);
+        let message = serde_json::to_string(&unregistered)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(message.contains("not registered"), "{message}");
+    }
+}
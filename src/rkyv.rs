@@ -0,0 +1,229 @@
+//! `rkyv` support, enabled by the `rkyv` feature
+//!
+//! [`SmallBox<T, Space>`] archives like a [`Box<T>`], as an [`ArchivedBox`] holding a relative
+//! pointer to the archived value. The archived form does not depend on `Space`, so a box can be
+//! deserialized into a different space. Sized values, slices and strings deserialize into the
+//! inline space if they fit, following the same rule as a copy into a new box.
+//!
+//! [`SmallBox<T, Space>`]: crate::SmallBox
+//! [`Box<T>`]: ::alloc::boxed::Box
+
+use ::rkyv::Archive;
+use ::rkyv::ArchiveUnsized;
+use ::rkyv::Deserialize;
+use ::rkyv::Place;
+use ::rkyv::Serialize;
+use ::rkyv::SerializeUnsized;
+use ::rkyv::boxed::ArchivedBox;
+use ::rkyv::boxed::BoxResolver;
+use ::rkyv::rancor::Fallible;
+
+use crate::SmallBox;
+use crate::inline::collect_slice;
+use crate::policy::FallbackPolicy;
+use crate::sptr;
+
+impl<T: ?Sized + ArchiveUnsized, Space, P> Archive for SmallBox<T, Space, P> {
+    type Archived = ArchivedBox<T::Archived>;
+    type Resolver = BoxResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        ArchivedBox::resolve_from_ref(&**self, resolver, out);
+    }
+}
+
+impl<T, Space, P, S> Serialize<S> for SmallBox<T, Space, P>
+where
+    T: ?Sized + SerializeUnsized<S>,
+    S: Fallible + ?Sized,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        ArchivedBox::serialize_from_ref(&**self, serializer)
+    }
+}
+
+impl<T, Space, P, D> Deserialize<SmallBox<T, Space, P>, D> for ArchivedBox<T::Archived>
+where
+    T: Archive,
+    T::Archived: Deserialize<T, D>,
+    P: FallbackPolicy,
+    D: Fallible + ?Sized,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<SmallBox<T, Space, P>, D::Error> {
+        self.get().deserialize(deserializer).map(SmallBox::new)
+    }
+}
+
+impl<T, Space, P, D> Deserialize<SmallBox<[T], Space, P>, D> for ArchivedBox<[T::Archived]>
+where
+    T: Archive,
+    T::Archived: Deserialize<T, D>,
+    P: FallbackPolicy,
+    D: Fallible + ?Sized,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<SmallBox<[T], Space, P>, D::Error> {
+        let mut elems = self.get().iter();
+        // `collect_slice` takes exactly `len` elements.
+        collect_slice(elems.len(), || {
+            elems.next().unwrap().deserialize(deserializer)
+        })
+    }
+}
+
+impl<Space, P, D> Deserialize<SmallBox<str, Space, P>, D> for ArchivedBox<str>
+where
+    P: FallbackPolicy,
+    D: Fallible + ?Sized,
+{
+    fn deserialize(&self, _: &mut D) -> Result<SmallBox<str, Space, P>, D::Error> {
+        let archived = self.get();
+        Ok(unsafe { SmallBox::new_copy(archived, sptr::from_ref(archived)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicUsize;
+    use core::sync::atomic::Ordering;
+
+    use ::alloc::string::String;
+    use ::alloc::string::ToString;
+    use ::rkyv::Archive;
+    use ::rkyv::Archived;
+    use ::rkyv::Deserialize;
+    use ::rkyv::Serialize;
+    use ::rkyv::rancor::Error;
+
+    use crate::SmallBox;
+    use crate::smallbox;
+    use crate::space::*;
+
+    #[derive(Archive, Serialize, Deserialize)]
+    struct Snapshot {
+        name: SmallBox<str, S2>,
+        point: SmallBox<(u64, u64), S1>,
+        samples: SmallBox<[u16], S1>,
+    }
+
+    fn from_bytes<T>(bytes: &[u8]) -> Result<T, Error>
+    where
+        T: Archive,
+        T::Archived: Deserialize<T, ::rkyv::api::high::HighDeserializer<Error>>,
+    {
+        unsafe { ::rkyv::from_bytes_unchecked::<T, Error>(bytes) }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let snapshot = Snapshot {
+            name: SmallBox::from_box("frame".into()),
+            point: SmallBox::new((3, 4)),
+            samples: smallbox!([1u16, 2, 3, 4]),
+        };
+        let bytes = ::rkyv::to_bytes::<Error>(&snapshot).unwrap();
+
+        let archived = unsafe { ::rkyv::access_unchecked::<Archived<Snapshot>>(&bytes) };
+        assert_eq!(&*archived.name, "frame");
+        assert_eq!(archived.samples.len(), 4);
+
+        let snapshot = ::rkyv::deserialize::<Snapshot, Error>(archived).unwrap();
+        assert!(!snapshot.name.is_heap());
+        assert_eq!(&*snapshot.name, "frame");
+        assert!(snapshot.point.is_heap());
+        assert_eq!(*snapshot.point, (3, 4));
+        assert!(!snapshot.samples.is_heap());
+        assert_eq!(*snapshot.samples, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_placement() {
+        let long: SmallBox<str, S1> = SmallBox::from_box("long enough to spill".into());
+        let bytes = ::rkyv::to_bytes::<Error>(&long).unwrap();
+        let long: SmallBox<str, S1> = from_bytes(&bytes).unwrap();
+        assert!(long.is_heap());
+        assert_eq!(&*long, "long enough to spill");
+
+        let samples: SmallBox<[u16], S1> = smallbox!([1u16, 2, 3, 4, 5]);
+        let bytes = ::rkyv::to_bytes::<Error>(&samples).unwrap();
+        let samples: SmallBox<[u16], S1> = from_bytes(&bytes).unwrap();
+        assert!(samples.is_heap());
+        assert_eq!(*samples, [1, 2, 3, 4, 5]);
+
+        // the archived form does not depend on the space
+        let wider: SmallBox<[u16], S4> = from_bytes(&bytes).unwrap();
+        assert!(!wider.is_heap());
+        assert_eq!(*wider, [1, 2, 3, 4, 5]);
+
+        // spilled heap storage is aligned like any other heap value, also for bytes
+        let bytes: SmallBox<[u8], S1> = smallbox!([7u8; 20]);
+        let archived = ::rkyv::to_bytes::<Error>(&bytes).unwrap();
+        let bytes: SmallBox<[u8], S1> = from_bytes(&archived).unwrap();
+        assert!(bytes.is_heap());
+        assert_eq!(bytes.as_ptr().align_offset(2), 0);
+        assert_eq!(*SmallBox::into_box(bytes), [7u8; 20]);
+
+        let empty: SmallBox<[u64], ()> = smallbox!([0u64; 0]);
+        let bytes = ::rkyv::to_bytes::<Error>(&empty).unwrap();
+        let empty: SmallBox<[u64], ()> = from_bytes(&bytes).unwrap();
+        assert!(empty.is_heap());
+        assert!(empty.is_empty());
+
+        let zsts: SmallBox<[()], S1> = smallbox!([(); 3]);
+        let bytes = ::rkyv::to_bytes::<Error>(&zsts).unwrap();
+        let zsts: SmallBox<[()], S1> = from_bytes(&bytes).unwrap();
+        assert!(!zsts.is_heap());
+        assert_eq!(zsts.len(), 3);
+    }
+
+    #[test]
+    fn test_error_drops_elements() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Archive, Serialize)]
+        struct Counted(u8);
+
+        impl<D: ::rkyv::rancor::Fallible + ?Sized> Deserialize<Counted, D> for ArchivedCounted
+        where D::Error: ::rkyv::rancor::Source
+        {
+            fn deserialize(&self, _: &mut D) -> Result<Counted, D::Error> {
+                if self.0 == 0 {
+                    ::rkyv::rancor::fail!(Failed);
+                }
+                Ok(Counted(self.0))
+            }
+        }
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        #[derive(Debug)]
+        struct Failed;
+
+        impl core::fmt::Display for Failed {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str("failed")
+            }
+        }
+
+        impl core::error::Error for Failed {}
+
+        let values: SmallBox<[Counted], S4> = smallbox!([Counted(1), Counted(2), Counted(0)]);
+        let bytes = ::rkyv::to_bytes::<Error>(&values).unwrap();
+        drop(values);
+        DROPS.store(0, Ordering::Relaxed);
+
+        let inline = from_bytes::<SmallBox<[Counted], S4>>(&bytes);
+        assert!(inline.is_err());
+        assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+
+        let spilled = from_bytes::<SmallBox<[Counted], S1>>(&bytes);
+        assert!(spilled.is_err());
+        assert_eq!(DROPS.load(Ordering::Relaxed), 4);
+
+        let message: String = spilled.err().unwrap().to_string();
+        assert!(message.contains("failed"), "{message}");
+    }
+}
@@ -0,0 +1,275 @@
+//! `serde` support, enabled by the `serde` feature
+//!
+//! [`SmallBox<T, Space>`] serializes as its value for any `T: ?Sized + Serialize`, and
+//! deserializes for sized `T`, `[T]` and `str`. Deserialized slices and strings are written
+//! directly into the inline space when they fit, and fall back to the heap otherwise. If the
+//! [`FallbackPolicy`] does not allow heap values, input that does not fit is an error instead of
+//! a panic.
+//!
+//! [`SmallBox<T, Space>`]: crate::SmallBox
+
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::str;
+
+use ::alloc::string::String;
+use ::alloc::vec::Vec;
+use ::serde::Deserialize;
+use ::serde::Deserializer;
+use ::serde::Serialize;
+use ::serde::Serializer;
+use ::serde::de;
+
+use crate::SmallBox;
+use crate::inline::InlineSlice;
+use crate::policy::FallbackPolicy;
+use crate::sptr;
+
+impl<T: ?Sized + Serialize, Space, P> Serialize for SmallBox<T, Space, P> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, Space, P: FallbackPolicy> Deserialize<'de>
+    for SmallBox<T, Space, P>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        check_fallback::<_, Space, P>(Layout::new::<T>())?;
+        T::deserialize(deserializer).map(SmallBox::new)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, Space, P: FallbackPolicy> Deserialize<'de>
+    for SmallBox<[T], Space, P>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(SliceVisitor(PhantomData))
+    }
+}
+
+impl<'de, Space, P: FallbackPolicy> Deserialize<'de> for SmallBox<str, Space, P> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(StrVisitor(PhantomData))
+    }
+}
+
+/// Returns an error for a value of `layout` that does not fit in `Space`, if the policy does not
+/// allow heap values.
+fn check_fallback<E: de::Error, Space, P: FallbackPolicy>(layout: Layout) -> Result<(), E> {
+    let space = Layout::new::<Space>();
+    if P::ALLOWS_HEAP || (layout.size() <= space.size() && layout.align() <= space.align()) {
+        return Ok(());
+    }
+    Err(E::custom(format_args!(
+        "value of {} bytes (align {}) does not fit in a space of {} bytes (align {})",
+        layout.size(),
+        layout.align(),
+        space.size(),
+        space.align()
+    )))
+}
+
+struct SliceVisitor<T, Space, P>(PhantomData<(T, Space, P)>);
+
+impl<'de, T: Deserialize<'de>, Space, P: FallbackPolicy> de::Visitor<'de>
+    for SliceVisitor<T, Space, P>
+{
+    type Value = SmallBox<[T], Space, P>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        // The size hint is capped so that an untrusted hint can not make us allocate a lot up
+        // front.
+        let capped_hint = |seq: &A| seq.size_hint().unwrap_or(0).min(4096);
+
+        let mut vec = if mem::align_of::<T>() > mem::align_of::<Space>() {
+            // The space can not hold an over-aligned `T`, and an empty slice of them still needs
+            // an aligned pointer, so the whole sequence goes to the heap.
+            check_fallback::<_, Space, P>(Layout::new::<[T; 0]>())?;
+            Vec::with_capacity(capped_hint(&seq))
+        } else {
+            let mut inline = InlineSlice::<T, Space>::new();
+            let spilled = loop {
+                let Some(elem) = seq.next_element()? else {
+                    return Ok(inline.into_smallbox());
+                };
+                if inline.len == InlineSlice::<T, Space>::CAPACITY {
+                    break elem;
+                }
+                inline.push(elem);
+            };
+
+            // The sequence does not fit in the space, continue on the heap.
+            let layout = Layout::array::<T>(inline.len + 1)
+                .map_err(|_| de::Error::custom("capacity overflow"))?;
+            check_fallback::<_, Space, P>(layout)?;
+            let mut vec = inline.into_vec(capped_hint(&seq) + 1);
+            vec.push(spilled);
+            vec
+        };
+        while let Some(elem) = seq.next_element()? {
+            vec.push(elem);
+        }
+        Ok(SmallBox::from_box(vec.into_boxed_slice()))
+    }
+}
+
+struct StrVisitor<Space, P>(PhantomData<(Space, P)>);
+
+impl<'de, Space, P: FallbackPolicy> de::Visitor<'de> for StrVisitor<Space, P> {
+    type Value = SmallBox<str, Space, P>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        check_fallback::<_, Space, P>(Layout::for_value(v))?;
+        Ok(unsafe { SmallBox::new_copy(v, sptr::from_ref(v)) })
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        if v.len() <= mem::size_of::<Space>() || !P::ALLOWS_HEAP {
+            self.visit_str(&v)
+        } else {
+            // Reuse the allocation of the string.
+            Ok(SmallBox::from_box(v.into_boxed_str()))
+        }
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        match str::from_utf8(v) {
+            Ok(s) => self.visit_str(s),
+            Err(_) => Err(E::invalid_value(de::Unexpected::Bytes(v), &self)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicUsize;
+    use core::sync::atomic::Ordering;
+
+    use ::alloc::string::String;
+    use ::alloc::string::ToString;
+    use ::alloc::vec;
+    use ::alloc::vec::Vec;
+    use ::serde::Deserialize;
+
+    use crate::SmallBox;
+    use crate::smallbox;
+    use crate::space::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let sized: SmallBox<(u32, String), S4> = SmallBox::new((7, "seven".to_string()));
+        let json = serde_json::to_string(&sized).unwrap();
+        assert_eq!(json, r#"[7,"seven"]"#);
+        let sized: SmallBox<(u32, String), S4> = serde_json::from_str(&json).unwrap();
+        assert_eq!(*sized, (7, "seven".to_string()));
+
+        let slice: SmallBox<[u16], S1> = smallbox!([1u16, 2, 3, 4]);
+        let json = serde_json::to_string(&slice).unwrap();
+        assert_eq!(json, "[1,2,3,4]");
+        let slice: SmallBox<[u16], S1> = serde_json::from_str(&json).unwrap();
+        assert!(!slice.is_heap());
+        assert_eq!(*slice, [1, 2, 3, 4]);
+
+        let string: SmallBox<str, S1> = serde_json::from_str(r#""hi""#).unwrap();
+        assert!(!string.is_heap());
+        assert_eq!(&*string, "hi");
+        assert_eq!(serde_json::to_string(&string).unwrap(), r#""hi""#);
+    }
+
+    #[test]
+    fn test_spill() {
+        let slice: SmallBox<[u16], S1> = serde_json::from_str("[1,2,3,4,5]").unwrap();
+        assert!(slice.is_heap());
+        assert_eq!(*slice, [1, 2, 3, 4, 5]);
+
+        let slice: SmallBox<[u64], ()> = serde_json::from_str("[]").unwrap();
+        assert!(slice.is_heap());
+        assert!(slice.is_empty());
+
+        #[derive(Deserialize)]
+        #[repr(align(64))]
+        struct Aligned;
+
+        let slice: SmallBox<[Aligned], S1> = serde_json::from_str("[]").unwrap();
+        assert!(slice.is_heap());
+        assert!(slice.is_empty());
+        assert!(slice.as_ptr().is_aligned());
+
+        let zsts: SmallBox<[()], S1> = serde_json::from_str("[null,null,null]").unwrap();
+        assert!(!zsts.is_heap());
+        assert_eq!(zsts.len(), 3);
+
+        // escaped strings are decoded into an owned string first
+        let string: SmallBox<str, S1> = serde_json::from_str(r#""a\"b""#).unwrap();
+        assert!(!string.is_heap());
+        assert_eq!(&*string, "a\"b");
+
+        let string: SmallBox<str, S1> = serde_json::from_str(r#""0123456789\n""#).unwrap();
+        assert!(string.is_heap());
+        assert_eq!(&*string, "0123456789\n");
+    }
+
+    #[test]
+    fn test_panic_on_heap() {
+        use crate::policy::PanicOnHeap;
+
+        let slice: SmallBox<[u16], S1, PanicOnHeap> = serde_json::from_str("[1,2,3,4]").unwrap();
+        assert_eq!(*slice, [1, 2, 3, 4]);
+        let string: SmallBox<str, S1, PanicOnHeap> = serde_json::from_str(r#""a\"b""#).unwrap();
+        assert_eq!(&*string, "a\"b");
+
+        let err = serde_json::from_str::<SmallBox<[u16], S1, PanicOnHeap>>("[1,2,3,4,5]");
+        assert!(err.unwrap_err().to_string().contains("does not fit"));
+        let err = serde_json::from_str::<SmallBox<[u64], (), PanicOnHeap>>("[]");
+        assert!(err.is_err());
+        let err = serde_json::from_str::<SmallBox<str, S1, PanicOnHeap>>(r#""012345678""#);
+        assert!(err.is_err());
+        let err = serde_json::from_str::<SmallBox<str, S1, PanicOnHeap>>(r#""0123456789\n""#);
+        assert!(err.is_err());
+        let err = serde_json::from_str::<SmallBox<[u64; 2], S1, PanicOnHeap>>("[1,2]");
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_error_drops_elements() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counted;
+        impl<'de> Deserialize<'de> for Counted {
+            fn deserialize<D: ::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<Self, D::Error> {
+                u8::deserialize(deserializer).map(|_| Counted)
+            }
+        }
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let inline: Result<SmallBox<[Counted], S4>, _> = serde_json::from_str("[1,2,\"x\"]");
+        assert!(inline.is_err());
+        assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+
+        let spilled: Result<SmallBox<[Counted], S1>, _> = serde_json::from_str("[1,2,\"x\"]");
+        assert!(spilled.is_err());
+        assert_eq!(DROPS.load(Ordering::Relaxed), 4);
+
+        let values: Vec<SmallBox<[u8], S1>> = serde_json::from_str("[[1],[2,3]]").unwrap();
+        assert_eq!(values.iter().map(|v| v.len()).collect::<Vec<_>>(), vec![
+            1, 2
+        ]);
+    }
+}
@@ -1,3 +1,4 @@
+use core::alloc::Layout;
 use core::any::Any;
 use core::cell::UnsafeCell;
 use core::cmp::Ordering;
@@ -6,6 +7,7 @@ use core::future::Future;
 use core::hash::Hash;
 use core::hash::{self};
 use core::hint::unreachable_unchecked;
+use core::iter::FusedIterator;
 use core::marker::PhantomData;
 #[cfg(feature = "coerce")]
 use core::marker::Unsize;
@@ -18,28 +20,36 @@ use core::ops::CoerceUnsized;
 use core::pin::Pin;
 use core::ptr;
 use core::ptr::NonNull;
+#[cfg(feature = "std")]
+use std::io;
 
-use ::alloc::alloc;
-use ::alloc::alloc::Layout;
-use ::alloc::alloc::handle_alloc_error;
+#[cfg(not(feature = "inline-only"))]
 use ::alloc::boxed::Box;
-
+#[cfg(not(feature = "inline-only"))]
+use ::alloc::rc::Rc;
+#[cfg(all(not(feature = "inline-only"), target_has_atomic = "ptr"))]
+use ::alloc::sync::Arc;
+
+use crate::HeaderSlice;
+use crate::heap;
+use crate::policy::AllowHeap;
+use crate::policy::FallbackPolicy;
 use crate::sptr;
 
 /// A sentinel pointer that signals that the value is stored on the stack
 ///
 /// It is never supposed to be dereferenced
-const INLINE_SENTINEL: *mut u8 = sptr::without_provenance_mut(0x1);
+pub(crate) const INLINE_SENTINEL: *mut u8 = sptr::without_provenance_mut(0x1);
 
 /// Minimum alignment for allocations
 ///
 /// Forcing a minimum alignment prevents the allocator
 /// from returning a pointer with the same address as `INLINE_SENTINEL`
-const MIN_ALIGNMENT: usize = 2;
+pub(crate) const MIN_ALIGNMENT: usize = 2;
 
 #[cfg(feature = "coerce")]
-impl<T: ?Sized + Unsize<U>, U: ?Sized, Space> CoerceUnsized<SmallBox<U, Space>>
-    for SmallBox<T, Space>
+impl<T: ?Sized + Unsize<U>, U: ?Sized, Space, P> CoerceUnsized<SmallBox<U, Space, P>>
+    for SmallBox<T, Space, P>
 {
 }
 
@@ -53,6 +63,9 @@ impl<T: ?Sized + Unsize<U>, U: ?Sized, Space> CoerceUnsized<SmallBox<U, Space>>
 /// You can think that it has the signature of `smallbox!<U: Sized, T: ?Sized>(val: U) ->
 /// SmallBox<T, Space>`
 ///
+/// `smallbox!(in arena, val)` creates an [`ArenaSmallBox`](crate::arena::ArenaSmallBox) instead,
+/// whose heap fallback is allocated in `arena`.
+///
 /// # Example
 ///
 /// ```
@@ -64,16 +77,28 @@ impl<T: ?Sized + Unsize<U>, U: ?Sized, Space> CoerceUnsized<SmallBox<U, Space>>
 /// use smallbox::space::*;
 ///
 /// let small: SmallBox<[usize], S4> = smallbox!([0usize; 2]);
-/// let large: SmallBox<[usize], S4> = smallbox!([1usize; 8]);
-///
 /// assert_eq!(small.len(), 2);
+///
+/// # #[cfg(not(feature = "inline-only"))]
+/// # {
+/// let large: SmallBox<[usize], S4> = smallbox!([1usize; 8]);
 /// assert_eq!(large[7], 1);
 ///
 /// assert!(large.is_heap() == true);
 /// # }
+/// # }
 /// ```
 #[macro_export]
 macro_rules! smallbox {
+    ( in $arena: expr, $e: expr ) => {{
+        let arena = $arena;
+        let val = $e;
+        let ptr = ::core::ptr::addr_of!(val);
+        #[allow(unsafe_code)]
+        unsafe {
+            $crate::arena::ArenaSmallBox::new_unchecked_in(val, ptr, arena)
+        }
+    }};
     ( $e: expr ) => {{
         let val = $e;
         let ptr = ::core::ptr::addr_of!(val);
@@ -85,19 +110,20 @@ macro_rules! smallbox {
 }
 
 /// An optimized box that store value on stack or on heap depending on its size
-pub struct SmallBox<T: ?Sized, Space> {
+pub struct SmallBox<T: ?Sized, Space, P = AllowHeap> {
     space: MaybeUninit<UnsafeCell<Space>>,
     ptr: NonNull<T>,
     _phantom: PhantomData<T>,
+    _policy: PhantomData<P>,
 }
 
-impl<T: Default, Space> Default for SmallBox<T, Space> {
+impl<T: Default, Space, P: FallbackPolicy> Default for SmallBox<T, Space, P> {
     fn default() -> Self {
         Self::new(T::default())
     }
 }
 
-impl<T: ?Sized, Space> SmallBox<T, Space> {
+impl<T: ?Sized, Space, P> SmallBox<T, Space, P> {
     /// Box value on stack or on heap depending on its size.
     ///
     /// # Example
@@ -107,25 +133,38 @@ impl<T: ?Sized, Space> SmallBox<T, Space> {
     /// use smallbox::space::*;
     ///
     /// let small: SmallBox<_, S4> = SmallBox::new([0usize; 2]);
-    /// let large: SmallBox<_, S4> = SmallBox::new([1usize; 8]);
-    ///
     /// assert_eq!(small.len(), 2);
+    ///
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
+    /// let large: SmallBox<_, S4> = SmallBox::new([1usize; 8]);
     /// assert_eq!(large[7], 1);
     ///
     /// assert!(large.is_heap() == true);
+    /// # }
     /// ```
     #[inline(always)]
-    pub fn new(val: T) -> SmallBox<T, Space>
-    where T: Sized {
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn new(val: T) -> SmallBox<T, Space, P>
+    where
+        T: Sized,
+        P: FallbackPolicy,
+    {
         smallbox!(val)
     }
 
     #[doc(hidden)]
     #[inline]
-    pub unsafe fn new_unchecked<U>(val: U, ptr: *const T) -> SmallBox<T, Space>
-    where U: Sized {
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub unsafe fn new_unchecked<U>(val: U, ptr: *const T) -> SmallBox<T, Space, P>
+    where
+        U: Sized,
+        P: FallbackPolicy,
+    {
+        #[cfg(feature = "inline-only")]
+        heap::assert_inline::<U, Space>();
         let val = ManuallyDrop::new(val);
-        Self::new_copy(&val, ptr)
+        Self::new_copy::<U>(&val, ptr)
     }
 
     /// Change the capacity of [`SmallBox`].
@@ -141,10 +180,18 @@ impl<T: ?Sized, Space> SmallBox<T, Space> {
     /// use smallbox::space::S2;
     /// use smallbox::space::S4;
     ///
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
     /// let s: SmallBox<_, S4> = SmallBox::new([0usize; 4]);
     /// let m: SmallBox<_, S2> = s.resize();
+    /// # }
     /// ```
-    pub fn resize<ToSpace>(self) -> SmallBox<T, ToSpace> {
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn resize<ToSpace>(self) -> SmallBox<T, ToSpace, P>
+    where P: FallbackPolicy {
+        // Without a heap, every value is inline and fits in any space that `Space` fits in.
+        #[cfg(feature = "inline-only")]
+        heap::assert_inline::<Space, ToSpace>();
         let this = ManuallyDrop::new(self);
 
         if this.is_heap() {
@@ -154,10 +201,18 @@ impl<T: ?Sized, Space> SmallBox<T, Space> {
                 space,
                 ptr: this.ptr,
                 _phantom: PhantomData,
+                _policy: PhantomData,
             }
         } else {
             let val: &T = &this;
-            unsafe { SmallBox::<T, ToSpace>::new_copy(val, sptr::from_ref(val)) }
+            let resized = unsafe { SmallBox::<T, ToSpace, P>::new_copy(val, sptr::from_ref(val)) };
+            #[cfg(feature = "zeroize")]
+            this.wipe_space();
+            #[cfg(feature = "stats")]
+            if resized.is_heap() {
+                crate::stats::record_resize_promotion();
+            }
+            resized
         }
     }
 
@@ -172,63 +227,266 @@ impl<T: ?Sized, Space> SmallBox<T, Space> {
     /// let stacked: SmallBox<usize, S1> = SmallBox::new(0usize);
     /// assert!(!stacked.is_heap());
     ///
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
     /// let heaped: SmallBox<(usize, usize), S1> = SmallBox::new((0usize, 1usize));
     /// assert!(heaped.is_heap());
+    /// # }
     /// ```
     #[inline]
     pub fn is_heap(&self) -> bool {
         self.ptr.as_ptr().cast::<u8>() != INLINE_SENTINEL
     }
 
-    unsafe fn new_copy<U>(val: &U, metadata_ptr: *const T) -> SmallBox<T, Space>
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub(crate) unsafe fn new_copy<U>(val: &U, metadata_ptr: *const T) -> SmallBox<T, Space, P>
+    where
+        U: ?Sized,
+        P: FallbackPolicy,
+    {
+        let layout = Layout::for_value::<U>(val);
+        let mut this = Self::new_uninit(layout, metadata_ptr);
+        #[cfg(feature = "audit")]
+        if this.is_heap() {
+            crate::audit::record::<U, Space>(layout);
+        }
+        ptr::copy_nonoverlapping(
+            sptr::from_ref(val).cast(),
+            SmallBox::as_mut_ptr(&mut this).cast::<u8>(),
+            layout.size(),
+        );
+        ManuallyDrop::into_inner(this)
+    }
+
+    /// Copies the value into a new heap allocation regardless of whether it would fit in `Space`.
+    #[cfg(not(feature = "inline-only"))]
+    #[cfg_attr(feature = "audit", track_caller)]
+    unsafe fn new_copy_heap<U>(val: &U, metadata_ptr: *const T) -> SmallBox<T, Space, P>
     where U: ?Sized {
         let layout = Layout::for_value::<U>(val);
+        let mut this = Self::new_uninit_heap(layout, metadata_ptr);
+        #[cfg(feature = "audit")]
+        crate::audit::record::<U, Space>(layout);
+        ptr::copy_nonoverlapping(
+            sptr::from_ref(val).cast(),
+            SmallBox::as_mut_ptr(&mut this).cast::<u8>(),
+            layout.size(),
+        );
+        ManuallyDrop::into_inner(this)
+    }
+
+    /// Creates a box with uninitialized storage for a value of `layout`, inline if it fits.
+    ///
+    /// The box is wrapped in `ManuallyDrop` because the caller has to initialize the value
+    /// before it can be dropped.
+    pub(crate) unsafe fn new_uninit(
+        layout: Layout,
+        metadata_ptr: *const T,
+    ) -> ManuallyDrop<SmallBox<T, Space, P>>
+    where
+        P: FallbackPolicy,
+    {
         let space_layout = Layout::new::<Space>();
 
-        let mut space = MaybeUninit::<UnsafeCell<Space>>::uninit();
+        if layout.size() > space_layout.size() || layout.align() > space_layout.align() {
+            P::on_heap_fallback(layout, space_layout);
+            return Self::new_uninit_heap(layout, metadata_ptr);
+        }
 
-        let (ptr_this, val_dst): (*mut u8, *mut u8) =
-            if layout.size() <= space_layout.size() && layout.align() <= space_layout.align() {
-                // Stack.
-                (INLINE_SENTINEL, space.as_mut_ptr().cast())
-            } else if layout.size() == 0 {
-                // ZST with alignment greater than Space, which will behave like being stored on
-                // heap but will not actually allocate.
-                (
-                    sptr::without_provenance_mut(layout.align()),
-                    sptr::without_provenance_mut(layout.align()),
-                )
-            } else {
-                // Heap.
-                let layout = layout
-                    // Safety: MIN_ALIGNMENT is 2, which is a valid power-of-two alignment.
-                    .align_to(MIN_ALIGNMENT)
-                    .unwrap_or_else(|_| unreachable_unchecked());
-                let heap_ptr = alloc::alloc(layout);
+        // Stack.
+        #[cfg(feature = "stats")]
+        crate::stats::record_inline();
 
-                if heap_ptr.is_null() {
-                    handle_alloc_error(layout)
-                }
+        // `self.ptr` always holds the metadata, even if stack allocated.
+        let ptr = sptr::with_metadata_of_mut(INLINE_SENTINEL, metadata_ptr);
 
-                (heap_ptr, heap_ptr)
-            };
+        ManuallyDrop::new(SmallBox {
+            space: MaybeUninit::uninit(),
+            // Safety: INLINE_SENTINEL is not null.
+            ptr: NonNull::new_unchecked(ptr),
+            _phantom: PhantomData,
+            _policy: PhantomData,
+        })
+    }
+
+    /// Creates a box with uninitialized heap storage for a value of `layout`.
+    unsafe fn new_uninit_heap(
+        layout: Layout,
+        metadata_ptr: *const T,
+    ) -> ManuallyDrop<SmallBox<T, Space, P>> {
+        #[cfg(feature = "stats")]
+        crate::stats::record_heap();
+
+        let ptr_this: *mut u8 = if layout.size() == 0 {
+            // ZST, which will behave like being stored on heap but will not actually allocate.
+            // The address is fixed, so it also stays put when the box is moved.
+            sptr::without_provenance_mut(layout.align().max(MIN_ALIGNMENT))
+        } else {
+            let layout = layout
+                // Safety: MIN_ALIGNMENT is 2, which is a valid power-of-two alignment.
+                .align_to(MIN_ALIGNMENT)
+                .unwrap_or_else(|_| unreachable_unchecked());
+            heap::alloc(layout)
+        };
 
         // `self.ptr` always holds the metadata, even if stack allocated.
         let ptr = sptr::with_metadata_of_mut(ptr_this, metadata_ptr);
-        // Safety: ptr is either INLINE_SENTINEL or returned from the allocator and checked for
-        // null.
-        let ptr = NonNull::new_unchecked(ptr);
 
-        ptr::copy_nonoverlapping(sptr::from_ref(val).cast(), val_dst, layout.size());
+        ManuallyDrop::new(SmallBox {
+            space: MaybeUninit::uninit(),
+            // Safety: ptr is either a non-zero alignment or returned from the allocator and
+            // checked for null.
+            ptr: NonNull::new_unchecked(ptr),
+            _phantom: PhantomData,
+            _policy: PhantomData,
+        })
+    }
+
+    /// Creates a box from an inline space that already holds a value with the metadata of
+    /// `metadata_ptr` at its start.
+    ///
+    /// The value must fit in `Space`.
+    #[cfg(any(
+        feature = "serde",
+        feature = "rkyv",
+        feature = "arbitrary",
+        feature = "proptest"
+    ))]
+    pub(crate) unsafe fn from_space(
+        space: MaybeUninit<UnsafeCell<Space>>,
+        metadata_ptr: *const T,
+    ) -> SmallBox<T, Space, P> {
+        #[cfg(feature = "stats")]
+        crate::stats::record_inline();
 
         SmallBox {
             space,
-            ptr,
+            // Safety: INLINE_SENTINEL is not null.
+            ptr: NonNull::new_unchecked(sptr::with_metadata_of_mut(INLINE_SENTINEL, metadata_ptr)),
             _phantom: PhantomData,
+            _policy: PhantomData,
         }
     }
 
-    unsafe fn downcast_unchecked<U: Any>(self) -> SmallBox<U, Space> {
+    /// Moves the value to the heap if it is stored inline.
+    #[cfg(not(feature = "inline-only"))]
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub(crate) fn into_heap(self) -> SmallBox<T, Space, P> {
+        if self.is_heap() {
+            return self;
+        }
+
+        let this = ManuallyDrop::new(self);
+        let val: &T = &this;
+        let heaped = unsafe { SmallBox::new_copy_heap(val, sptr::from_ref(val)) };
+        #[cfg(feature = "zeroize")]
+        this.wipe_space();
+        heaped
+    }
+
+    /// Moves the value to the heap for a generator of the `arbitrary` or `proptest` features, if
+    /// the policy allows heap values.
+    ///
+    /// Unlike [`SmallBox::into_heap`], the move is reported to the policy like a fallback.
+    #[cfg(any(feature = "arbitrary", feature = "proptest"))]
+    pub(crate) fn into_heap_if_allowed(self) -> SmallBox<T, Space, P>
+    where P: FallbackPolicy {
+        if !P::ALLOWS_HEAP || self.is_heap() {
+            return self;
+        }
+        P::on_heap_fallback(Layout::for_value::<T>(&self), Layout::new::<Space>());
+        self.into_heap()
+    }
+
+    /// Reinterprets the bytes of the value as a slice of `len` elements of `U`, which must cover
+    /// the same number of bytes.
+    ///
+    /// An inline value stays inline if `U` is not more aligned than `Space`, and a heap allocation
+    /// is reused if its layout does not change. Otherwise the bytes are copied into a new box.
+    #[cfg(feature = "bytemuck")]
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub(crate) unsafe fn cast_bytes_unchecked<U>(self, len: usize) -> SmallBox<[U], Space, P>
+    where P: FallbackPolicy {
+        #[cfg(feature = "inline-only")]
+        const {
+            assert!(
+                mem::align_of::<U>() <= mem::align_of::<Space>(),
+                "the cast slice is more aligned than the space, and the \
+                 `inline-only` feature disables the heap fallback"
+            )
+        }
+        let this = ManuallyDrop::new(self);
+        let layout = Layout::for_value::<T>(&this)
+            .align_to(MIN_ALIGNMENT)
+            .unwrap_or_else(|_| unreachable_unchecked());
+        let cast_layout = Layout::array::<U>(len).unwrap_or_else(|_| unreachable_unchecked());
+        debug_assert_eq!(layout.size(), cast_layout.size());
+        let metadata_ptr = ptr::slice_from_raw_parts(ptr::null::<U>(), len);
+
+        if this.is_heap() {
+            let aligned = cast_layout
+                .align_to(MIN_ALIGNMENT)
+                .unwrap_or_else(|_| unreachable_unchecked());
+            if layout == aligned {
+                return SmallBox {
+                    space: MaybeUninit::uninit(),
+                    ptr: NonNull::new_unchecked(sptr::with_metadata_of_mut(
+                        this.ptr.as_ptr().cast::<u8>(),
+                        metadata_ptr,
+                    )),
+                    _phantom: PhantomData,
+                    _policy: PhantomData,
+                };
+            }
+        } else if cast_layout.align() <= mem::align_of::<Space>() {
+            let space = ptr::read(&this.space);
+            #[cfg(feature = "zeroize")]
+            this.wipe_space();
+            // Safety: INLINE_SENTINEL is not null.
+            return SmallBox {
+                space,
+                ptr: NonNull::new_unchecked(sptr::with_metadata_of_mut(
+                    INLINE_SENTINEL,
+                    metadata_ptr,
+                )),
+                _phantom: PhantomData,
+                _policy: PhantomData,
+            };
+        }
+
+        let mut cast = SmallBox::<[U], Space, P>::new_uninit(cast_layout, metadata_ptr);
+        #[cfg(feature = "audit")]
+        if cast.is_heap() {
+            crate::audit::record::<[U], Space>(cast_layout);
+        }
+        ptr::copy_nonoverlapping(
+            SmallBox::as_ptr(&this).cast::<u8>(),
+            SmallBox::as_mut_ptr(&mut cast).cast::<u8>(),
+            cast_layout.size(),
+        );
+        #[cfg(feature = "zeroize")]
+        this.wipe_space();
+        if this.is_heap() && layout.size() != 0 {
+            heap::dealloc(this.ptr.as_ptr().cast::<u8>(), layout);
+        }
+        ManuallyDrop::into_inner(cast)
+    }
+
+    /// Overwrites an inline value with zeros, after it has been moved out or dropped.
+    #[cfg(feature = "zeroize")]
+    #[inline]
+    fn wipe_space(&self) {
+        if !self.is_heap() {
+            unsafe {
+                crate::zeroize::wipe(
+                    UnsafeCell::raw_get(self.space.as_ptr()).cast(),
+                    mem::size_of::<Space>(),
+                );
+            }
+        }
+    }
+
+    unsafe fn downcast_unchecked<U: Any>(self) -> SmallBox<U, Space, P> {
         let this = ManuallyDrop::new(self);
 
         let size = mem::size_of::<U>();
@@ -241,6 +499,8 @@ impl<T: ?Sized, Space> SmallBox<T, Space> {
                 size,
             );
         };
+        #[cfg(feature = "zeroize")]
+        this.wipe_space();
 
         let ptr = this.ptr.cast();
 
@@ -248,24 +508,173 @@ impl<T: ?Sized, Space> SmallBox<T, Space> {
             space,
             ptr,
             _phantom: PhantomData,
+            _policy: PhantomData,
         }
     }
 
+    /// Returns a raw pointer to the boxed value.
+    ///
+    /// An inline value moves together with the [`SmallBox`], so the pointer is only valid until
+    /// the box is moved or dropped. This is an associated function to avoid shadowing methods of
+    /// `T`, so it has to be called as `SmallBox::as_ptr(&b)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let small: SmallBox<_, S1> = SmallBox::new(42usize);
+    /// let ptr = SmallBox::as_ptr(&small);
+    /// assert_eq!(unsafe { *ptr }, 42);
+    /// ```
     #[inline]
-    unsafe fn as_ptr(&self) -> *const T {
-        if self.is_heap() {
-            self.ptr.as_ptr()
+    pub fn as_ptr(this: &Self) -> *const T {
+        if this.is_heap() {
+            this.ptr.as_ptr()
         } else {
-            sptr::with_metadata_of(self.space.as_ptr(), self.ptr.as_ptr())
+            sptr::with_metadata_of(this.space.as_ptr(), this.ptr.as_ptr())
         }
     }
 
+    /// Returns a raw mutable pointer to the boxed value.
+    ///
+    /// See [`SmallBox::as_ptr`] for how long the pointer stays valid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let mut small: SmallBox<_, S1> = SmallBox::new(42usize);
+    /// let ptr = SmallBox::as_mut_ptr(&mut small);
+    /// unsafe { *ptr = 7 };
+    /// assert_eq!(*small, 7);
+    /// ```
     #[inline]
-    unsafe fn as_mut_ptr(&mut self) -> *mut T {
-        if self.is_heap() {
-            self.ptr.as_ptr()
+    pub fn as_mut_ptr(this: &mut Self) -> *mut T {
+        if this.is_heap() {
+            this.ptr.as_ptr()
         } else {
-            sptr::with_metadata_of_mut(self.space.as_mut_ptr(), self.ptr.as_ptr())
+            sptr::with_metadata_of_mut(this.space.as_mut_ptr(), this.ptr.as_ptr())
+        }
+    }
+
+    /// Returns true if the two boxes point to the same address.
+    ///
+    /// Like [`ptr::addr_eq`], the metadata (vtable or length) is ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let a: SmallBox<_, S1> = SmallBox::new(42usize);
+    /// let b: SmallBox<_, S1> = SmallBox::new(42usize);
+    /// assert!(SmallBox::ptr_eq(&a, &a));
+    /// assert!(!SmallBox::ptr_eq(&a, &b));
+    /// ```
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        ptr::addr_eq(Self::as_ptr(this), Self::as_ptr(other))
+    }
+
+    /// Returns a null pointer that carries the metadata of the boxed value.
+    ///
+    /// For trait objects this is the vtable and for slices the length; for sized types there is
+    /// no metadata. The returned pointer must never be dereferenced, it is only meant to be
+    /// passed along with the address of the value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate smallbox;
+    ///
+    /// # fn main() {
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S4;
+    ///
+    /// let slice: SmallBox<[u8], S4> = smallbox!([1u8, 2, 3]);
+    /// let meta = SmallBox::metadata(&slice);
+    /// assert!(meta.is_null());
+    /// assert_eq!(meta.len(), 3);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn metadata(this: &Self) -> *const T {
+        sptr::with_metadata_of(ptr::null::<u8>(), this.ptr.as_ptr())
+    }
+
+    /// Consumes and leaks the [`SmallBox`], returning a mutable reference to the value.
+    ///
+    /// The value is moved to the heap first if it is stored inline, so that the reference
+    /// outlives the box. Dropping the value and freeing the memory can be done by converting
+    /// the reference back with [`SmallBox::from_raw`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let small: SmallBox<_, S1> = SmallBox::new(42usize);
+    /// let leaked: &'static mut usize = SmallBox::leak(small);
+    /// *leaked += 1;
+    /// assert_eq!(*leaked, 43);
+    /// ```
+    #[cfg(not(feature = "inline-only"))]
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn leak<'a>(b: Self) -> &'a mut T
+    where T: 'a {
+        unsafe { &mut *Self::into_raw(b) }
+    }
+
+    /// Consumes the [`SmallBox`], returning a raw pointer to the value on the heap.
+    ///
+    /// The value is moved to the heap first if it is stored inline. The caller is responsible
+    /// for the memory, which is best released by converting the pointer back with
+    /// [`SmallBox::from_raw`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S1;
+    ///
+    /// let small: SmallBox<_, S1> = SmallBox::new(42usize);
+    /// let raw = SmallBox::into_raw(small);
+    /// let small: SmallBox<_, S1> = unsafe { SmallBox::from_raw(raw) };
+    /// assert!(small.is_heap());
+    /// assert_eq!(*small, 42);
+    /// ```
+    #[cfg(not(feature = "inline-only"))]
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn into_raw(b: Self) -> *mut T {
+        let mut this = ManuallyDrop::new(b.into_heap());
+        Self::as_mut_ptr(&mut this)
+    }
+
+    /// Constructs a [`SmallBox`] from a raw pointer.
+    ///
+    /// The resulting box is always heap-resident.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must have been returned by [`SmallBox::into_raw`] or [`SmallBox::leak`] for a box
+    /// of the same `T` (with any `Space`), and must not be used after this call.
+    #[cfg(not(feature = "inline-only"))]
+    #[inline]
+    pub unsafe fn from_raw(raw: *mut T) -> Self {
+        SmallBox {
+            space: MaybeUninit::uninit(),
+            ptr: NonNull::new_unchecked(raw),
+            _phantom: PhantomData,
+            _policy: PhantomData,
         }
     }
 
@@ -280,15 +689,20 @@ impl<T: ?Sized, Space> SmallBox<T, Space> {
     /// let val = stacked.into_inner();
     /// assert_eq!(val[0], 21);
     ///
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
     /// let boxed: SmallBox<_, S1> = SmallBox::new(vec![21, 56, 420]);
     /// let val = boxed.into_inner();
     /// assert_eq!(val[1], 56);
+    /// # }
     /// ```
     #[inline]
     pub fn into_inner(self) -> T
     where T: Sized {
         let this = ManuallyDrop::new(self);
-        let ret_val: T = unsafe { this.as_ptr().read() };
+        let ret_val: T = unsafe { SmallBox::as_ptr(&this).read() };
+        #[cfg(feature = "zeroize")]
+        this.wipe_space();
 
         // Just deallocates the heap memory without dropping the boxed value
         if this.is_heap() && mem::size_of::<T>() != 0 {
@@ -299,7 +713,7 @@ impl<T: ?Sized, Space> SmallBox<T, Space> {
                     .unwrap_or_else(|_| unreachable_unchecked())
             };
             unsafe {
-                alloc::dealloc(this.ptr.as_ptr().cast::<u8>(), layout);
+                heap::dealloc(this.ptr.as_ptr().cast::<u8>(), layout);
             }
         }
 
@@ -312,6 +726,13 @@ impl<T: ?Sized, Space> SmallBox<T, Space> {
     /// This method transfers ownership from the [`Box`] to the [`SmallBox`] without copying
     /// or moving the data.
     ///
+    /// The heap storage of a [`SmallBox`] is aligned to at least 2 bytes, so a value with an
+    /// alignment of 1 is the exception: it is copied into a new allocation, and the [`Box`]
+    /// allocation is freed. With the `pool` feature, so is a non-empty value of up to 512 bytes
+    /// and alignment 16, which is copied into a pooled block.
+    ///
+    /// The fallback policy is only called if the value does not fit in `Space`.
+    ///
     /// # Example
     ///
     /// ```
@@ -327,24 +748,48 @@ impl<T: ?Sized, Space> SmallBox<T, Space> {
     /// assert!(small_box.is_heap());
     /// assert_eq!(*small_box, [1, 2, 3, 4]);
     /// ```
-    pub fn from_box(boxed: ::alloc::boxed::Box<T>) -> Self {
+    #[cfg(not(feature = "inline-only"))]
+    pub fn from_box(boxed: Box<T>) -> Self
+    where P: FallbackPolicy {
+        let (layout, space_layout) = (Layout::for_value::<T>(&boxed), Layout::new::<Space>());
+        if layout.size() > space_layout.size() || layout.align() > space_layout.align() {
+            P::on_heap_fallback(layout, space_layout);
+        }
+
+        #[cfg(feature = "stats")]
+        crate::stats::record_adopt(mem::size_of_val::<T>(&boxed));
+
         unsafe {
-            let ptr = NonNull::new_unchecked(Box::into_raw(boxed));
+            let ptr = Box::into_raw(boxed);
+            let layout = Layout::for_value::<T>(&*ptr);
+            let aligned = layout
+                .align_to(MIN_ALIGNMENT)
+                .unwrap_or_else(|_| unreachable_unchecked());
+            let ptr = sptr::with_metadata_of_mut(heap::adopt(ptr.cast(), layout, aligned), ptr);
+            let ptr = NonNull::new_unchecked(ptr);
             let space = MaybeUninit::<UnsafeCell<Space>>::uninit();
             SmallBox {
                 space,
                 ptr,
                 _phantom: PhantomData,
+                _policy: PhantomData,
             }
         }
     }
 
     /// Converts a [`SmallBox`] into a standard [`Box`].
     ///
+    /// There is no `From<SmallBox<T, Space>> for Box<T>` implementation, since the orphan rules
+    /// reject it for the fundamental [`Box`] type.
+    ///
     /// If the data is stored on the stack, it will be moved to the heap.
     /// If the data is already on the heap, ownership is transferred without
     /// copying or moving the data.
     ///
+    /// A heap value with an alignment of 1 is copied into a new [`Box`] allocation, since its heap
+    /// storage is aligned to 2 bytes. With the `pool` feature, so is a heap value stored in a
+    /// pooled block (non-empty, up to 512 bytes and alignment 16).
+    ///
     /// # Example
     ///
     /// ```
@@ -359,16 +804,290 @@ impl<T: ?Sized, Space> SmallBox<T, Space> {
     ///
     /// assert_eq!(*boxed, [1, 2, 3, 4]);
     /// ```
-    pub fn into_box(boxed: SmallBox<T, Space>) -> ::alloc::boxed::Box<T> {
+    #[cfg(not(feature = "inline-only"))]
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn into_box(boxed: SmallBox<T, Space, P>) -> Box<T> {
+        #[cfg(feature = "stats")]
+        {
+            if !boxed.is_heap() {
+                crate::stats::record_into_box_promotion();
+            }
+            crate::stats::record_release(mem::size_of_val::<T>(&boxed));
+        }
+
+        unsafe {
+            let mut enforce_heap = ManuallyDrop::new(boxed.into_heap());
+            debug_assert!(enforce_heap.is_heap());
+            let ptr = SmallBox::as_mut_ptr(&mut enforce_heap);
+            let layout = Layout::for_value::<T>(&*ptr);
+            let aligned = layout
+                .align_to(MIN_ALIGNMENT)
+                .unwrap_or_else(|_| unreachable_unchecked());
+            Box::from_raw(sptr::with_metadata_of_mut(
+                heap::release(ptr.cast(), layout, aligned),
+                ptr,
+            ))
+        }
+    }
+
+    /// Converts a [`SmallBox`] into an [`Rc`].
+    ///
+    /// The value is copied once, from the inline space or the heap straight into a new [`Rc`]
+    /// allocation, and the heap memory of the [`SmallBox`] is released. Only values aligned to
+    /// more than 4096 bytes take a detour through [`SmallBox::into_box`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate smallbox;
+    ///
+    /// # fn main() {
+    /// use std::rc::Rc;
+    ///
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S4;
+    ///
+    /// let small_box: SmallBox<[i32], S4> = smallbox!([1, 2, 3, 4]);
+    /// let rc: Rc<[i32]> = SmallBox::into_rc(small_box);
+    ///
+    /// assert_eq!(*rc, [1, 2, 3, 4]);
+    /// # }
+    /// ```
+    #[cfg(not(feature = "inline-only"))]
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn into_rc(boxed: SmallBox<T, Space, P>) -> Rc<T> {
+        boxed
+            .into_shared()
+            .unwrap_or_else(|boxed| Rc::from(Self::into_box(boxed)))
+    }
+
+    /// Converts a [`SmallBox`] into an [`Arc`].
+    ///
+    /// See [`SmallBox::into_rc`] for how the value is copied.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate smallbox;
+    ///
+    /// # fn main() {
+    /// use std::fmt::Display;
+    /// use std::sync::Arc;
+    ///
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S4;
+    ///
+    /// let small_box: SmallBox<dyn Display + Send + Sync, S4> = smallbox!(42);
+    /// let arc: Arc<dyn Display + Send + Sync> = SmallBox::into_arc(small_box);
+    ///
+    /// assert_eq!(arc.to_string(), "42");
+    /// # }
+    /// ```
+    #[cfg(all(not(feature = "inline-only"), target_has_atomic = "ptr"))]
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn into_arc(boxed: SmallBox<T, Space, P>) -> Arc<T> {
+        boxed
+            .into_shared()
+            .unwrap_or_else(|boxed| Arc::from(Self::into_box(boxed)))
+    }
+
+    /// Copies the value into a new shared allocation and frees the storage of `self` without
+    /// dropping the value, or gives `self` back if the value is aligned to more than 4096 bytes.
+    #[cfg(not(feature = "inline-only"))]
+    fn into_shared<R: Shared<T>>(self) -> Result<R, Self> {
+        let this = ManuallyDrop::new(self);
+        let layout = Layout::for_value::<T>(&this);
+        let src = SmallBox::as_ptr(&this);
+
+        unsafe {
+            let Some(data) = copy_to_shared::<T, R>(src.cast(), layout) else {
+                return Err(ManuallyDrop::into_inner(this));
+            };
+            #[cfg(feature = "zeroize")]
+            this.wipe_space();
+            if this.is_heap() && layout.size() != 0 {
+                let layout = layout
+                    .align_to(MIN_ALIGNMENT)
+                    .unwrap_or_else(|_| unreachable_unchecked());
+                heap::dealloc(this.ptr.as_ptr().cast::<u8>(), layout);
+            }
+            Ok(R::from_raw(sptr::with_metadata_of(data, src)))
+        }
+    }
+
+    /// Constructs a new `Pin<SmallBox<T, Space>>`.
+    ///
+    /// A value stored inline moves together with its [`SmallBox`], so the value is always placed
+    /// on the heap to uphold the pinning guarantees, no matter how large `Space` is. If `T:
+    /// Unpin`, [`Pin::new`] can be used instead to keep the value inline.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::pin::Pin;
+    ///
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S4;
+    ///
+    /// let fut: Pin<SmallBox<_, S4>> = SmallBox::pin(async { 42 });
+    /// assert_eq!(futures::executor::block_on(fut), 42);
+    /// ```
+    #[cfg(not(feature = "inline-only"))]
+    #[inline]
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn pin(val: T) -> Pin<SmallBox<T, Space, P>>
+    where T: Sized {
+        let val = ManuallyDrop::new(val);
+        // Safety: the value is on the heap (or is a ZST with a fixed address), so it will not
+        // move when the returned `SmallBox` moves.
+        unsafe { Pin::new_unchecked(Self::new_copy_heap(&*val, sptr::from_ref(&*val))) }
+    }
+
+    /// Converts a `SmallBox<T, Space>` into a `Pin<SmallBox<T, Space>>`.
+    ///
+    /// If the value is stored inline, it will be moved to the heap first, for the same reason as
+    /// in [`SmallBox::pin`]. If the value is already on the heap, it is not moved.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate smallbox;
+    ///
+    /// # fn main() {
+    /// use core::future::Future;
+    /// use core::pin::Pin;
+    ///
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S4;
+    ///
+    /// let fut: SmallBox<dyn Future<Output = i32>, S4> = smallbox!(async { 42 });
+    /// let pinned = SmallBox::into_pin(fut);
+    /// assert_eq!(futures::executor::block_on(pinned), 42);
+    /// # }
+    /// ```
+    #[cfg(not(feature = "inline-only"))]
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn into_pin(boxed: SmallBox<T, Space, P>) -> Pin<SmallBox<T, Space, P>> {
+        // Safety: the value is on the heap (or is a ZST with a fixed address), so it will not
+        // move when the returned `SmallBox` moves.
+        unsafe { Pin::new_unchecked(boxed.into_heap()) }
+    }
+}
+
+impl<T: ?Sized + HeaderSlice, Space, P: FallbackPolicy> SmallBox<T, Space, P> {
+    /// Creates a custom dynamically sized value from a header and the elements of a slice.
+    ///
+    /// The value is stored inline if the header and all elements fit in `Space`, otherwise it is
+    /// allocated on the heap. See [`HeaderSlice`] for how to declare such a type.
+    ///
+    /// # Panics
+    ///
+    /// With the `inline-only` feature, panics if the value does not fit in `Space`. Its size is
+    /// only known at runtime, so this can not be checked at compile time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::HeaderSlice;
+    /// use smallbox::SmallBox;
+    /// use smallbox::space::S2;
+    ///
+    /// #[repr(C)]
+    /// struct Named {
+    ///     name: String,
+    ///     values: [u64],
+    /// }
+    ///
+    /// unsafe impl HeaderSlice for Named {
+    ///     type Header = String;
+    ///     type Element = u64;
+    ///
+    ///     fn cast_slice_ptr(ptr: *mut [u64]) -> *mut Named {
+    ///         ptr as *mut Named
+    ///     }
+    /// }
+    ///
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
+    /// let named: SmallBox<Named, S2> = SmallBox::from_header_and_slice("primes".into(), &[2, 3, 5]);
+    /// assert!(named.is_heap());
+    /// assert_eq!(named.name, "primes");
+    /// assert_eq!(named.values, [2, 3, 5]);
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "audit", track_caller)]
+    pub fn from_header_and_slice(header: T::Header, slice: &[T::Element]) -> SmallBox<T, Space, P>
+    where T::Element: Clone {
+        /// Drops the initialized part of the value and frees the heap memory if cloning an
+        /// element panics.
+        struct Guard<T: ?Sized + HeaderSlice, Space, P> {
+            this: ManuallyDrop<SmallBox<T, Space, P>>,
+            layout: Layout,
+            offset: usize,
+            initialized: usize,
+        }
+
+        impl<T: ?Sized + HeaderSlice, Space, P> Drop for Guard<T, Space, P> {
+            fn drop(&mut self) {
+                unsafe {
+                    let dst = SmallBox::as_mut_ptr(&mut self.this).cast::<u8>();
+                    ptr::drop_in_place(dst.cast::<T::Header>());
+                    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(
+                        dst.add(self.offset).cast::<T::Element>(),
+                        self.initialized,
+                    ));
+
+                    let layout = self
+                        .layout
+                        .align_to(MIN_ALIGNMENT)
+                        .unwrap_or_else(|_| unreachable_unchecked());
+                    if self.this.is_heap() && layout.size() != 0 {
+                        heap::dealloc(dst, layout);
+                    }
+                }
+            }
+        }
+
+        let (layout, offset) = Layout::array::<T::Element>(slice.len())
+            .and_then(|array| Layout::new::<T::Header>().extend(array))
+            .expect("capacity overflow");
+        let layout = layout.pad_to_align();
+        let metadata_ptr = T::cast_slice_ptr(ptr::slice_from_raw_parts_mut(
+            NonNull::<T::Element>::dangling().as_ptr(),
+            slice.len(),
+        ));
+
         unsafe {
-            let mut enforce_heap = ManuallyDrop::new(boxed.resize::<()>());
-            debug_assert!(enforce_heap.is_heap());
-            Box::from_raw(enforce_heap.as_mut_ptr())
+            let mut guard = Guard {
+                this: Self::new_uninit(layout, metadata_ptr),
+                layout,
+                offset,
+                initialized: 0,
+            };
+            #[cfg(feature = "audit")]
+            if guard.this.is_heap() {
+                crate::audit::record::<T, Space>(layout);
+            }
+
+            let dst = SmallBox::as_mut_ptr(&mut guard.this).cast::<u8>();
+            dst.cast::<T::Header>().write(header);
+            let elements = dst.add(offset).cast::<T::Element>();
+            for (i, element) in slice.iter().enumerate() {
+                elements.add(i).write(element.clone());
+                guard.initialized += 1;
+            }
+
+            let this = ptr::read(&guard.this);
+            mem::forget(guard);
+            ManuallyDrop::into_inner(this)
         }
     }
 }
 
-impl<Space> SmallBox<dyn Any, Space> {
+impl<Space, P> SmallBox<dyn Any, Space, P> {
     /// Attempt to downcast the box to a concrete type.
     ///
     /// # Examples
@@ -397,7 +1116,7 @@ impl<Space> SmallBox<dyn Any, Space> {
     /// # }
     /// ```
     #[inline]
-    pub fn downcast<T: Any>(self) -> Result<SmallBox<T, Space>, Self> {
+    pub fn downcast<T: Any>(self) -> Result<SmallBox<T, Space, P>, Self> {
         if self.is::<T>() {
             unsafe { Ok(self.downcast_unchecked()) }
         } else {
@@ -406,7 +1125,7 @@ impl<Space> SmallBox<dyn Any, Space> {
     }
 }
 
-impl<Space> SmallBox<dyn Any + Send, Space> {
+impl<Space, P> SmallBox<dyn Any + Send, Space, P> {
     /// Attempt to downcast the box to a concrete type.
     ///
     /// # Examples
@@ -435,7 +1154,7 @@ impl<Space> SmallBox<dyn Any + Send, Space> {
     /// # }
     /// ```
     #[inline]
-    pub fn downcast<T: Any>(self) -> Result<SmallBox<T, Space>, Self> {
+    pub fn downcast<T: Any>(self) -> Result<SmallBox<T, Space, P>, Self> {
         if self.is::<T>() {
             unsafe { Ok(self.downcast_unchecked()) }
         } else {
@@ -444,21 +1163,21 @@ impl<Space> SmallBox<dyn Any + Send, Space> {
     }
 }
 
-impl<T: ?Sized, Space> ops::Deref for SmallBox<T, Space> {
+impl<T: ?Sized, Space, P> ops::Deref for SmallBox<T, Space, P> {
     type Target = T;
 
     fn deref(&self) -> &T {
-        unsafe { &*self.as_ptr() }
+        unsafe { &*SmallBox::as_ptr(self) }
     }
 }
 
-impl<T: ?Sized, Space> ops::DerefMut for SmallBox<T, Space> {
+impl<T: ?Sized, Space, P> ops::DerefMut for SmallBox<T, Space, P> {
     fn deref_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.as_mut_ptr() }
+        unsafe { &mut *SmallBox::as_mut_ptr(self) }
     }
 }
 
-impl<T: ?Sized, Space> ops::Drop for SmallBox<T, Space> {
+impl<T: ?Sized, Space, P> ops::Drop for SmallBox<T, Space, P> {
     fn drop(&mut self) {
         unsafe {
             let layout = Layout::for_value::<T>(&*self)
@@ -466,14 +1185,16 @@ impl<T: ?Sized, Space> ops::Drop for SmallBox<T, Space> {
                 .unwrap_or_else(|_| unreachable_unchecked());
 
             ptr::drop_in_place::<T>(&mut **self);
+            #[cfg(feature = "zeroize")]
+            self.wipe_space();
             if self.is_heap() && layout.size() != 0 {
-                alloc::dealloc(self.ptr.as_ptr().cast::<u8>(), layout);
+                heap::dealloc(self.ptr.as_ptr().cast::<u8>(), layout);
             }
         }
     }
 }
 
-impl<T: Clone, Space> Clone for SmallBox<T, Space>
+impl<T: Clone, Space, P: FallbackPolicy> Clone for SmallBox<T, Space, P>
 where T: Sized
 {
     fn clone(&self) -> Self {
@@ -482,19 +1203,19 @@ where T: Sized
     }
 }
 
-impl<T: ?Sized + fmt::Display, Space> fmt::Display for SmallBox<T, Space> {
+impl<T: ?Sized + fmt::Display, Space, P> fmt::Display for SmallBox<T, Space, P> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(&**self, f)
     }
 }
 
-impl<T: ?Sized + fmt::Debug, Space> fmt::Debug for SmallBox<T, Space> {
+impl<T: ?Sized + fmt::Debug, Space, P> fmt::Debug for SmallBox<T, Space, P> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Debug::fmt(&**self, f)
     }
 }
 
-impl<T: ?Sized, Space> fmt::Pointer for SmallBox<T, Space> {
+impl<T: ?Sized, Space, P> fmt::Pointer for SmallBox<T, Space, P> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         // It's not possible to extract the inner Unique directly from the Box,
         // instead we cast it to a *const which aliases the Unique
@@ -503,80 +1224,347 @@ impl<T: ?Sized, Space> fmt::Pointer for SmallBox<T, Space> {
     }
 }
 
-impl<T: ?Sized + PartialEq, Space> PartialEq for SmallBox<T, Space> {
-    fn eq(&self, other: &SmallBox<T, Space>) -> bool {
+impl<T: ?Sized + PartialEq, Space, P> PartialEq for SmallBox<T, Space, P> {
+    fn eq(&self, other: &SmallBox<T, Space, P>) -> bool {
         PartialEq::eq(&**self, &**other)
     }
 }
 
-impl<T: ?Sized + PartialOrd, Space> PartialOrd for SmallBox<T, Space> {
-    fn partial_cmp(&self, other: &SmallBox<T, Space>) -> Option<Ordering> {
+impl<T: ?Sized + PartialOrd, Space, P> PartialOrd for SmallBox<T, Space, P> {
+    fn partial_cmp(&self, other: &SmallBox<T, Space, P>) -> Option<Ordering> {
         PartialOrd::partial_cmp(&**self, &**other)
     }
-    fn lt(&self, other: &SmallBox<T, Space>) -> bool {
+    fn lt(&self, other: &SmallBox<T, Space, P>) -> bool {
         PartialOrd::lt(&**self, &**other)
     }
-    fn le(&self, other: &SmallBox<T, Space>) -> bool {
+    fn le(&self, other: &SmallBox<T, Space, P>) -> bool {
         PartialOrd::le(&**self, &**other)
     }
-    fn ge(&self, other: &SmallBox<T, Space>) -> bool {
+    fn ge(&self, other: &SmallBox<T, Space, P>) -> bool {
         PartialOrd::ge(&**self, &**other)
     }
-    fn gt(&self, other: &SmallBox<T, Space>) -> bool {
+    fn gt(&self, other: &SmallBox<T, Space, P>) -> bool {
         PartialOrd::gt(&**self, &**other)
     }
 }
 
-impl<T: ?Sized + Ord, Space> Ord for SmallBox<T, Space> {
-    fn cmp(&self, other: &SmallBox<T, Space>) -> Ordering {
+impl<T: ?Sized + Ord, Space, P> Ord for SmallBox<T, Space, P> {
+    fn cmp(&self, other: &SmallBox<T, Space, P>) -> Ordering {
         Ord::cmp(&**self, &**other)
     }
 }
 
-impl<T: ?Sized + Eq, Space> Eq for SmallBox<T, Space> {}
+impl<T: ?Sized + Eq, Space, P> Eq for SmallBox<T, Space, P> {}
 
-impl<T: ?Sized + Hash, Space> Hash for SmallBox<T, Space> {
+impl<T: ?Sized + Hash, Space, P> Hash for SmallBox<T, Space, P> {
     fn hash<H: hash::Hasher>(&self, state: &mut H) {
         (**self).hash(state);
     }
 }
 
+#[cfg(not(feature = "inline-only"))]
+impl<T: ?Sized, Space, P: FallbackPolicy> From<Box<T>> for SmallBox<T, Space, P> {
+    /// Converts a [`Box`] into a [`SmallBox`]. See [`SmallBox::from_box`].
+    fn from(boxed: Box<T>) -> Self {
+        SmallBox::from_box(boxed)
+    }
+}
+
+#[cfg(not(feature = "inline-only"))]
+impl<T: ?Sized, Space, P> From<SmallBox<T, Space, P>> for Rc<T> {
+    /// Converts a [`SmallBox`] into an [`Rc`]. See [`SmallBox::into_rc`].
+    fn from(boxed: SmallBox<T, Space, P>) -> Self {
+        SmallBox::into_rc(boxed)
+    }
+}
+
+#[cfg(all(not(feature = "inline-only"), target_has_atomic = "ptr"))]
+impl<T: ?Sized, Space, P> From<SmallBox<T, Space, P>> for Arc<T> {
+    /// Converts a [`SmallBox`] into an [`Arc`]. See [`SmallBox::into_arc`].
+    fn from(boxed: SmallBox<T, Space, P>) -> Self {
+        SmallBox::into_arc(boxed)
+    }
+}
+
+/// A shared pointer that [`SmallBox::into_rc`] and [`SmallBox::into_arc`] copy values into
+#[cfg(not(feature = "inline-only"))]
+trait Shared<T: ?Sized> {
+    /// Copies `len` chunks from `src` into a new allocation and leaks it.
+    unsafe fn copy_from_chunks<C: Copy>(src: *const C, len: usize) -> *const C;
+
+    /// Takes back the allocation leaked by `copy_from_chunks`, with a value of the same size and
+    /// alignment as the chunks.
+    unsafe fn from_raw(ptr: *const T) -> Self;
+}
+
+#[cfg(not(feature = "inline-only"))]
+impl<T: ?Sized> Shared<T> for Rc<T> {
+    unsafe fn copy_from_chunks<C: Copy>(src: *const C, len: usize) -> *const C {
+        // `Range` is `TrustedLen`, so this allocates the `Rc` directly without a `Vec`
+        let mut rc: Rc<[MaybeUninit<C>]> = (0..len).map(|_| MaybeUninit::uninit()).collect();
+        let dst = Rc::get_mut(&mut rc).unwrap_or_else(|| unreachable_unchecked());
+        ptr::copy_nonoverlapping(src, dst.as_mut_ptr().cast::<C>(), len);
+        Rc::into_raw(rc).cast::<C>()
+    }
+
+    unsafe fn from_raw(ptr: *const T) -> Self {
+        Rc::from_raw(ptr)
+    }
+}
+
+#[cfg(all(not(feature = "inline-only"), target_has_atomic = "ptr"))]
+impl<T: ?Sized> Shared<T> for Arc<T> {
+    unsafe fn copy_from_chunks<C: Copy>(src: *const C, len: usize) -> *const C {
+        let mut arc: Arc<[MaybeUninit<C>]> = (0..len).map(|_| MaybeUninit::uninit()).collect();
+        let dst = Arc::get_mut(&mut arc).unwrap_or_else(|| unreachable_unchecked());
+        ptr::copy_nonoverlapping(src, dst.as_mut_ptr().cast::<C>(), len);
+        Arc::into_raw(arc).cast::<C>()
+    }
+
+    unsafe fn from_raw(ptr: *const T) -> Self {
+        Arc::from_raw(ptr)
+    }
+}
+
+macro_rules! aligned_chunks {
+    ($($chunk:ident = $align:literal),+ $(,)?) => {
+        $(
+            // Only used as the element type of an uninitialized slice
+            #[cfg(not(feature = "inline-only"))]
+            #[allow(dead_code)]
+            #[derive(Clone, Copy)]
+            #[repr(C, align($align))]
+            struct $chunk([u8; $align]);
+        )+
+
+        /// Copies the value at `src` into a new shared allocation with the alignment of `layout`
+        ///
+        /// [`Rc::from_raw`] accepts a pointer from an `Rc<U>` of any `U` with the same size and
+        /// alignment as the value, so the allocation is made as a slice of chunks with the
+        /// alignment of the value. Returns `None` if no chunk has that alignment.
+        #[cfg(not(feature = "inline-only"))]
+        unsafe fn copy_to_shared<T: ?Sized, R: Shared<T>>(
+            src: *const u8,
+            layout: Layout,
+        ) -> Option<*const u8> {
+            let len = layout.size() / layout.align();
+            match layout.align() {
+                $($align => Some(R::copy_from_chunks(src.cast::<$chunk>(), len).cast()),)+
+                _ => None,
+            }
+        }
+    };
+}
+
+aligned_chunks!(
+    Chunk1 = 1,
+    Chunk2 = 2,
+    Chunk4 = 4,
+    Chunk8 = 8,
+    Chunk16 = 16,
+    Chunk32 = 32,
+    Chunk64 = 64,
+    Chunk128 = 128,
+    Chunk256 = 256,
+    Chunk512 = 512,
+    Chunk1024 = 1024,
+    Chunk2048 = 2048,
+    Chunk4096 = 4096,
+);
+
+#[cfg(not(feature = "inline-only"))]
+impl<T: ?Sized, Space, P> From<SmallBox<T, Space, P>> for Pin<SmallBox<T, Space, P>> {
+    /// Converts a `SmallBox<T, Space>` into a `Pin<SmallBox<T, Space>>`.
+    ///
+    /// This moves the value to the heap if it is stored inline. See [`SmallBox::into_pin`].
+    fn from(boxed: SmallBox<T, Space, P>) -> Self {
+        SmallBox::into_pin(boxed)
+    }
+}
+
 // We can implement Future for SmallBox soundly, even though it's not implemented for std Box.
 // The reason why it's not implemented for std Box is only because Box<T>: Unpin unconditionally,
 // even when T: !Unpin, which always allows getting &mut Box<T> from Pin<&mut Box<T>>.
 // For SmallBox, this is not the case, because it might carry the data on the stack, so if T:
 // !Unpin, then SmallBox<T>: !Unpin also. That means you can't get &mut SmallBox<T> from Pin<&mut
 // SmallBox<T>> in safe code, so it's safe to implement Future for SmallBox directly.
-impl<F: Future + ?Sized, S> Future for SmallBox<F, S> {
+// Note that an owning `Pin<SmallBox<T>>` is a different story: it can be moved around freely, so
+// `SmallBox::pin` and `SmallBox::into_pin` always place the value on the heap.
+// The same reasoning applies to the other poll-based traits, such as `Stream` and `AsyncRead`.
+impl<F: Future + ?Sized, S, P> Future for SmallBox<F, S, P> {
     type Output = F::Output;
 
     fn poll(
         self: Pin<&mut Self>,
         cx: &mut core::task::Context<'_>,
     ) -> core::task::Poll<Self::Output> {
+        self.project().poll(cx)
+    }
+}
+
+impl<T: ?Sized, Space, P> SmallBox<T, Space, P> {
+    /// Projects a pinned box to its pinned value, for forwarding poll-based traits.
+    #[inline]
+    pub(crate) fn project(self: Pin<&mut Self>) -> Pin<&mut T> {
         // Safety: When the SmallBox is pinned, the data on the stack is pinned.
         // The data on the heap is also pinned naturally, so all Pin guarantees are satisfied.
-        unsafe { Pin::new_unchecked(&mut **self.get_unchecked_mut()) }.poll(cx)
+        unsafe { Pin::new_unchecked(&mut **self.get_unchecked_mut()) }
+    }
+}
+
+impl<I: Iterator + ?Sized, Space, P> Iterator for SmallBox<I, Space, P> {
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        (**self).next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (**self).size_hint()
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<I::Item> {
+        (**self).nth(n)
+    }
+}
+
+impl<I: DoubleEndedIterator + ?Sized, Space, P> DoubleEndedIterator for SmallBox<I, Space, P> {
+    #[inline]
+    fn next_back(&mut self) -> Option<I::Item> {
+        (**self).next_back()
+    }
+
+    #[inline]
+    fn nth_back(&mut self, n: usize) -> Option<I::Item> {
+        (**self).nth_back(n)
+    }
+}
+
+impl<I: ExactSizeIterator + ?Sized, Space, P> ExactSizeIterator for SmallBox<I, Space, P> {
+    #[inline]
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
+
+impl<I: FusedIterator + ?Sized, Space, P> FusedIterator for SmallBox<I, Space, P> {}
+
+#[cfg(feature = "std")]
+impl<R: io::Read + ?Sized, Space, P> io::Read for SmallBox<R, Space, P> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read(buf)
+    }
+
+    #[inline]
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        (**self).read_vectored(bufs)
+    }
+
+    #[inline]
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        (**self).read_to_end(buf)
+    }
+
+    #[inline]
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        (**self).read_to_string(buf)
+    }
+
+    #[inline]
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        (**self).read_exact(buf)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write + ?Sized, Space, P> io::Write for SmallBox<W, Space, P> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (**self).write(buf)
+    }
+
+    #[inline]
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        (**self).write_vectored(bufs)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        (**self).write_all(buf)
+    }
+
+    #[inline]
+    fn write_fmt(&mut self, fmt: fmt::Arguments<'_>) -> io::Result<()> {
+        (**self).write_fmt(fmt)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: io::Seek + ?Sized, Space, P> io::Seek for SmallBox<S, Space, P> {
+    #[inline]
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        (**self).seek(pos)
+    }
+
+    #[inline]
+    fn stream_position(&mut self) -> io::Result<u64> {
+        (**self).stream_position()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<B: io::BufRead + ?Sized, Space, P> io::BufRead for SmallBox<B, Space, P> {
+    #[inline]
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        (**self).fill_buf()
+    }
+
+    #[inline]
+    fn consume(&mut self, amt: usize) {
+        (**self).consume(amt)
+    }
+
+    #[inline]
+    fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> io::Result<usize> {
+        (**self).read_until(byte, buf)
+    }
+
+    #[inline]
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        (**self).read_line(buf)
     }
 }
 
-unsafe impl<T: ?Sized + Send, Space> Send for SmallBox<T, Space> {}
-unsafe impl<T: ?Sized + Sync, Space> Sync for SmallBox<T, Space> {}
+unsafe impl<T: ?Sized + Send, Space, P> Send for SmallBox<T, Space, P> {}
+unsafe impl<T: ?Sized + Sync, Space, P> Sync for SmallBox<T, Space, P> {}
 
 #[cfg(test)]
 mod tests {
+    #[cfg(not(feature = "inline-only"))]
     use core::any::Any;
     use core::mem;
     use core::ptr::addr_of;
 
     use ::alloc::boxed::Box;
+    #[cfg(not(feature = "inline-only"))]
     use ::alloc::vec;
+    #[cfg(not(feature = "inline-only"))]
     use ::alloc::vec::Vec;
 
     use super::SmallBox;
     use crate::space::*;
 
     #[test]
+    #[cfg(not(feature = "inline-only"))]
     fn test_basic() {
         let stacked: SmallBox<usize, S1> = SmallBox::new(1234usize);
         assert!(*stacked == 1234);
@@ -586,6 +1574,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "inline-only"))]
     fn test_new_unchecked() {
         let val = [0usize, 1];
         let ptr = addr_of!(val);
@@ -613,6 +1602,7 @@ mod tests {
 
     #[test]
     #[deny(unsafe_code)]
+    #[cfg(not(feature = "inline-only"))]
     fn test_macro() {
         let stacked: SmallBox<dyn Any, S1> = smallbox!(1234usize);
         if let Some(num) = stacked.downcast_ref::<usize>() {
@@ -635,6 +1625,7 @@ mod tests {
 
     #[test]
     #[cfg(feature = "coerce")]
+    #[cfg(not(feature = "inline-only"))]
     fn test_coerce() {
         let stacked: SmallBox<dyn Any, S1> = SmallBox::new(1234usize);
         if let Some(num) = stacked.downcast_ref::<usize>() {
@@ -652,6 +1643,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "inline-only"))]
     fn test_drop() {
         use core::cell::Cell;
 
@@ -692,6 +1684,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "inline-only"))]
     fn test_oversize() {
         let fit = SmallBox::<_, S1>::new([1usize]);
         let oversize = SmallBox::<_, S1>::new([1usize, 2]);
@@ -700,6 +1693,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "inline-only"))]
     fn test_resize() {
         let m = SmallBox::<_, S4>::new([1usize, 2]);
         let l = m.resize::<S8>();
@@ -722,6 +1716,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "inline-only"))]
     fn test_zst() {
         struct ZSpace;
 
@@ -735,6 +1730,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "inline-only"))]
     fn test_downcast() {
         let stacked: SmallBox<dyn Any, S1> = smallbox!(0x01u32);
         assert!(!stacked.is_heap());
@@ -771,6 +1767,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "inline-only"))]
     fn test_into_inner() {
         let tester: SmallBox<_, S1> = SmallBox::new([21usize]);
         let val = tester.into_inner();
@@ -797,6 +1794,306 @@ mod tests {
         assert_eq!(futures::executor::block_on(boxed_fut), 123);
     }
 
+    #[test]
+    fn test_iterator() {
+        fn sum(values: impl Iterator<Item = u32>) -> u32 {
+            values.sum()
+        }
+
+        let mut iter: SmallBox<dyn DoubleEndedIterator<Item = u32>, S2> = smallbox!(1..=6);
+        assert!(!iter.is_heap());
+        assert_eq!(iter.size_hint(), (6, Some(6)));
+        assert_eq!(iter.nth(1), Some(2));
+        assert_eq!(iter.next_back(), Some(6));
+        assert_eq!(iter.nth_back(1), Some(4));
+        assert_eq!(sum(iter), 3);
+
+        let mut exact: SmallBox<_, S4> = SmallBox::new([1u32, 2, 3].into_iter());
+        assert_eq!(exact.len(), 3);
+        exact.next();
+        assert_eq!(exact.len(), 2);
+
+        fn assert_fused<I: core::iter::FusedIterator>(_: &I) {}
+        assert_fused(&exact);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_io() {
+        use std::io::BufRead;
+        use std::io::Cursor;
+        use std::io::IoSlice;
+        use std::io::IoSliceMut;
+        use std::io::Read;
+        use std::io::Seek;
+        use std::io::SeekFrom;
+        use std::io::Write;
+
+        let mut reader: SmallBox<dyn Read + Send, S4> = smallbox!(Cursor::new(b"abcdef".to_vec()));
+        assert!(!reader.is_heap());
+        let mut head = [0; 2];
+        reader.read_exact(&mut head).unwrap();
+        assert_eq!(&head, b"ab");
+        let (mut first, mut second) = ([0; 1], [0; 2]);
+        let read = reader
+            .read_vectored(&mut [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)])
+            .unwrap();
+        assert_eq!((read, &first, &second), (3, b"c", b"de"));
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "f");
+
+        let mut lines: SmallBox<dyn BufRead, S4> = smallbox!(Cursor::new("one\ntwo\n"));
+        let mut line = String::new();
+        lines.read_line(&mut line).unwrap();
+        assert_eq!(line, "one\n");
+        assert_eq!(lines.fill_buf().unwrap(), b"two\n");
+        lines.consume(4);
+        assert!(lines.fill_buf().unwrap().is_empty());
+
+        let mut writer: SmallBox<_, S4> = SmallBox::new(Cursor::new(Vec::new()));
+        writer.write_all(b"xyz").unwrap();
+        write!(writer, "{}", 42).unwrap();
+        let written = writer
+            .write_vectored(&[IoSlice::new(b"!"), IoSlice::new(b"?")])
+            .unwrap();
+        assert_eq!(written, 2);
+        writer.flush().unwrap();
+        assert_eq!(writer.stream_position().unwrap(), 7);
+        writer.seek(SeekFrom::Start(1)).unwrap();
+        writer.write_all(b"Y").unwrap();
+        assert_eq!(writer.into_inner().into_inner(), b"xYz42!?");
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_pin() {
+        use core::future::Future;
+        use core::marker::PhantomPinned;
+        use core::pin::Pin;
+
+        struct Pinned(u8, PhantomPinned);
+
+        let pinned: Pin<SmallBox<_, S4>> = SmallBox::pin(Pinned(1, PhantomPinned));
+        let addr = addr_of!(*pinned);
+        let moved = Box::new(pinned);
+        assert_eq!(addr, addr_of!(**moved));
+        assert_eq!(moved.0, 1);
+
+        let stacked: SmallBox<dyn Future<Output = u8>, S4> = smallbox!(async { 42 });
+        assert!(!stacked.is_heap());
+        let pinned = SmallBox::into_pin(stacked);
+        assert_eq!(futures::executor::block_on(pinned), 42);
+
+        let pinned: Pin<SmallBox<_, S4>> = SmallBox::new(async { 7 }).into();
+        assert_eq!(futures::executor::block_on(pinned), 7);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_shared_conversions() {
+        use ::alloc::rc::Rc;
+        use ::alloc::sync::Arc;
+
+        let stacked: SmallBox<[usize], S4> = smallbox!([1usize, 2]);
+        let rc: Rc<[usize]> = stacked.into();
+        assert_eq!(*rc, [1, 2]);
+
+        let heaped: SmallBox<dyn Any + Send + Sync, S1> = smallbox!([1usize, 2]);
+        assert!(heaped.is_heap());
+        let arc: Arc<dyn Any + Send + Sync> = heaped.into();
+        assert_eq!(arc.downcast_ref::<[usize; 2]>(), Some(&[1, 2]));
+
+        let small_box: SmallBox<_, S4> = Box::new([1usize, 2]).into();
+        assert!(small_box.is_heap());
+        let boxed: Box<[usize; 2]> = SmallBox::into_box(small_box);
+        assert_eq!(*boxed, [1, 2]);
+
+        #[derive(Debug, PartialEq)]
+        struct Zst;
+        let zst: SmallBox<Zst, ()> = SmallBox::new(Zst);
+        assert!(!zst.is_heap());
+        assert_eq!(*SmallBox::into_box(zst), Zst);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_from_box_unaligned() {
+        // `Box` allocations with an alignment of 1 and dangling `Box` pointers can not be adopted
+        // as they are.
+        let boxed: Box<str> = "unaligned".into();
+        let small_box: SmallBox<str, S1> = boxed.into();
+        assert!(small_box.is_heap());
+        assert!(!SmallBox::as_ptr(&small_box).cast::<u8>().is_null());
+        assert_eq!(&*small_box, "unaligned");
+        assert_eq!(&*SmallBox::into_box(small_box), "unaligned");
+
+        let empty: Box<[u8]> = Box::new([]);
+        let small_box: SmallBox<[u8], S1> = empty.into();
+        assert!(small_box.is_heap());
+        assert!(small_box.is_empty());
+        assert!(SmallBox::into_box(small_box).is_empty());
+
+        #[derive(Debug, PartialEq)]
+        struct Zst;
+        let small_box: SmallBox<Zst, S1> = Box::new(Zst).into();
+        assert!(small_box.is_heap());
+        assert_eq!(*small_box, Zst);
+        assert_eq!(*SmallBox::into_box(small_box), Zst);
+
+        let bytes: SmallBox<[u8], S1> = Box::<[u8]>::from([7u8; 33]).into();
+        assert_eq!(*SmallBox::into_box(bytes), [7; 33]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_into_shared() {
+        use core::cell::Cell;
+
+        use ::alloc::rc::Rc;
+        use ::alloc::string::String;
+        use ::alloc::string::ToString;
+        use ::alloc::sync::Arc;
+
+        struct Struct<'a>(&'a Cell<usize>, u8);
+        impl<'a> Drop for Struct<'a> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        let stacked: SmallBox<Struct, S1> = SmallBox::new(Struct(&drops, 1));
+        let rc = SmallBox::into_rc(stacked);
+        assert_eq!(drops.get(), 0);
+        assert_eq!(rc.1, 1);
+        drop(rc);
+        assert_eq!(drops.get(), 1);
+
+        let heaped: SmallBox<[Struct], S1> =
+            smallbox!([Struct(&drops, 2), Struct(&drops, 3), Struct(&drops, 4)]);
+        assert!(heaped.is_heap());
+        let rc = SmallBox::into_rc(heaped);
+        assert_eq!(drops.get(), 1);
+        assert_eq!(rc.iter().map(|s| s.1).collect::<Vec<_>>(), [2, 3, 4]);
+        drop(rc);
+        assert_eq!(drops.get(), 4);
+
+        let stacked: SmallBox<[u16], S4> = smallbox!([1u16, 2, 3]);
+        assert!(!stacked.is_heap());
+        let arc: Arc<[u16]> = SmallBox::into_arc(stacked);
+        assert_eq!(*arc, [1, 2, 3]);
+
+        let string: SmallBox<dyn core::fmt::Display, S4> = smallbox!(String::from("display"));
+        let rc: Rc<dyn core::fmt::Display> = SmallBox::into_rc(string);
+        assert_eq!(rc.to_string(), "display");
+
+        let empty: SmallBox<[u8], ()> = smallbox!([]);
+        assert!(SmallBox::into_rc(empty).is_empty());
+
+        #[repr(align(64))]
+        struct Aligned(u8);
+        let aligned: SmallBox<Aligned, S4> = SmallBox::new(Aligned(5));
+        let arc = SmallBox::into_arc(aligned);
+        assert!(crate::sptr::from_ref(&*arc).is_aligned());
+        assert_eq!(arc.0, 5);
+
+        #[repr(align(8192))]
+        struct Huge(u8);
+        let huge: SmallBox<Huge, S4> = SmallBox::new(Huge(6));
+        let rc = SmallBox::into_rc(huge);
+        assert!(crate::sptr::from_ref(&*rc).is_aligned());
+        assert_eq!(rc.0, 6);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_raw_parts() {
+        use core::cell::Cell;
+
+        struct Struct<'a>(&'a Cell<bool>, u8);
+        impl<'a> Drop for Struct<'a> {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        let flag = Cell::new(false);
+        let stacked: SmallBox<_, S2> = SmallBox::new(Struct(&flag, 1));
+        assert!(!stacked.is_heap());
+        let raw = SmallBox::into_raw(stacked);
+        assert!(!flag.get());
+        let heaped: SmallBox<_, S2> = unsafe { SmallBox::from_raw(raw) };
+        assert!(heaped.is_heap());
+        assert_eq!(heaped.1, 1);
+        drop(heaped);
+        assert!(flag.get());
+
+        let stacked: SmallBox<dyn Any, S2> = smallbox!(1u8);
+        let leaked = SmallBox::leak(stacked);
+        assert_eq!(leaked.downcast_ref::<u8>(), Some(&1));
+        let heaped: SmallBox<dyn Any, S1> = unsafe { SmallBox::from_raw(leaked) };
+        assert!(heaped.is_heap());
+
+        let slice: SmallBox<[u8], S1> = smallbox!([1u8, 2, 3]);
+        assert_eq!(SmallBox::metadata(&slice).len(), 3);
+        assert!(SmallBox::ptr_eq(&slice, &slice));
+        assert_eq!(SmallBox::as_ptr(&slice).cast::<u8>(), slice.as_ptr());
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_header_and_slice() {
+        use core::cell::Cell;
+
+        use crate::HeaderSlice;
+
+        #[derive(Clone)]
+        struct Counted<'a>(&'a Cell<usize>);
+        impl<'a> Drop for Counted<'a> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        #[repr(C)]
+        struct Packet<'a> {
+            header: Counted<'a>,
+            body: [Counted<'a>],
+        }
+
+        unsafe impl<'a> HeaderSlice for Packet<'a> {
+            type Header = Counted<'a>;
+            type Element = Counted<'a>;
+
+            #[allow(clippy::as_conversions)]
+            fn cast_slice_ptr(ptr: *mut [Counted<'a>]) -> *mut Packet<'a> {
+                ptr as *mut Packet<'a>
+            }
+        }
+
+        let drops = Cell::new(0);
+        let body = [Counted(&drops), Counted(&drops)];
+
+        let stacked: SmallBox<Packet, S4> = SmallBox::from_header_and_slice(Counted(&drops), &body);
+        assert!(!stacked.is_heap());
+        assert_eq!(stacked.body.len(), 2);
+        assert_eq!(mem::size_of_val(&*stacked), 3 * mem::size_of::<usize>());
+        drop(stacked);
+        assert_eq!(drops.get(), 3);
+
+        let heaped: SmallBox<Packet, S2> = SmallBox::from_header_and_slice(Counted(&drops), &body);
+        assert!(heaped.is_heap());
+        drop(heaped);
+        assert_eq!(drops.get(), 6);
+
+        let empty: SmallBox<Packet, S1> = SmallBox::from_header_and_slice(Counted(&drops), &[]);
+        assert!(!empty.is_heap());
+        assert!(empty.body.is_empty());
+        drop(empty);
+        assert_eq!(drops.get(), 7);
+    }
+
     #[test]
     fn test_variance() {
         #[allow(dead_code)]
@@ -834,6 +2131,24 @@ mod tests {
         assert_eq!(zst_addr % 512, 0);
     }
 
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_overaligned_space() {
+        #[repr(align(32))]
+        struct Overaligned([u8; 32]);
+
+        let heaped: SmallBox<_, S8> = SmallBox::new(Overaligned([1; 32]));
+        assert!(heaped.is_heap());
+
+        let stacked: SmallBox<_, Align32<S8>> = SmallBox::new(Overaligned([1; 32]));
+        assert!(!stacked.is_heap());
+        let moved = Box::new(stacked);
+        #[allow(clippy::as_conversions)]
+        let addr = addr_of!(**moved) as usize;
+        assert_eq!(addr % 32, 0);
+        assert_eq!(moved.0, [1; 32]);
+    }
+
     #[test]
     fn test_null_ptr_optimization() {
         assert_eq!(
@@ -843,6 +2158,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "inline-only"))]
     fn test_box_roundtrip() {
         // Box -> SmallBox -> Box
         let original_data = vec![1, 2, 3, 4, 5];
@@ -866,4 +2182,141 @@ mod tests {
         let final_data: &Vec<i32> = final_small_box.downcast_ref().unwrap();
         assert_eq!(original_data, *final_data);
     }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_fallback_policy() {
+        use crate::policy::PanicOnHeap;
+        use crate::policy::tests::CountFallbacks;
+        use crate::policy::tests::fallbacks_by;
+
+        let stacked: SmallBox<_, S2, PanicOnHeap> = SmallBox::new([0usize; 2]);
+        assert!(!stacked.is_heap());
+
+        let mut stacked = None;
+        assert_eq!(
+            fallbacks_by(|| {
+                stacked = Some::<SmallBox<dyn Any, S4, CountFallbacks>>(smallbox!([0usize; 4]))
+            }),
+            0
+        );
+        let mut heaped = None;
+        assert_eq!(
+            fallbacks_by(|| heaped = Some(stacked.unwrap().resize::<S1>())),
+            1
+        );
+        let heaped = heaped.unwrap();
+        assert!(heaped.is_heap());
+
+        // already on the heap, nothing falls back
+        assert_eq!(fallbacks_by(|| drop(heaped.resize::<S8>())), 0);
+
+        let mut adopted = None;
+        assert_eq!(
+            fallbacks_by(|| {
+                adopted = Some::<SmallBox<[u8], S1, CountFallbacks>>(SmallBox::from_box(
+                    vec![0u8; 32].into(),
+                ))
+            }),
+            1
+        );
+        assert!(adopted.unwrap().is_heap());
+
+        // fits inline, so adopting the box is not a fallback
+        let mut adopted = None;
+        assert_eq!(
+            fallbacks_by(|| {
+                adopted = Some::<SmallBox<[u8], S4, CountFallbacks>>(SmallBox::from_box(
+                    vec![0u8; 32].into(),
+                ))
+            }),
+            0
+        );
+        assert!(adopted.unwrap().is_heap());
+        let adopted: SmallBox<_, S1, PanicOnHeap> = SmallBox::from_box(Box::new(1usize));
+        assert_eq!(*adopted, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit in a space")]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_panic_on_heap() {
+        use crate::policy::PanicOnHeap;
+
+        let _: SmallBox<_, S1, PanicOnHeap> = SmallBox::new([0usize; 2]);
+    }
+
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn test_zeroize() {
+        use core::mem::ManuallyDrop;
+        use core::slice;
+
+        let mut stacked: ManuallyDrop<SmallBox<[u8], S2>> =
+            ManuallyDrop::new(smallbox!([0xAAu8; 16]));
+        assert!(!stacked.is_heap());
+        unsafe { ManuallyDrop::drop(&mut stacked) };
+
+        let space = unsafe {
+            slice::from_raw_parts(stacked.space.as_ptr().cast::<u8>(), mem::size_of::<S2>())
+        };
+        assert!(space.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    #[cfg(all(feature = "zeroize", not(feature = "inline-only")))]
+    fn test_zeroize_moves() {
+        use crate::zeroize::tests::wiped_by;
+
+        // Inline values are wiped from the space, heap values from their block when it is freed.
+        let space = mem::size_of::<S2>();
+        let block = mem::size_of::<[u64; 4]>();
+        let pooled = if cfg!(feature = "pool") { block } else { 0 };
+
+        let stacked: SmallBox<[u64; 2], S2> = SmallBox::new([1, 2]);
+        assert_eq!(wiped_by(|| assert_eq!(stacked.into_inner(), [1, 2])), space);
+        let heaped: SmallBox<[u64; 4], S2> = SmallBox::new([1; 4]);
+        assert_eq!(wiped_by(|| assert_eq!(heaped.into_inner(), [1; 4])), block);
+
+        let heaped: SmallBox<[u64; 4], S2> = SmallBox::new([1; 4]);
+        assert!(heaped.is_heap());
+        assert_eq!(wiped_by(|| drop(heaped)), block);
+
+        let stacked: SmallBox<[u64; 2], S2> = SmallBox::new([1, 2]);
+        let mut resized = None;
+        assert_eq!(wiped_by(|| resized = Some(stacked.resize::<S1>())), space);
+        assert!(resized.unwrap().is_heap());
+        let heaped: SmallBox<[u64; 4], S2> = SmallBox::new([1; 4]);
+        let resized = || assert_eq!(heaped.resize::<S1>().into_inner(), [1; 4]);
+        assert_eq!(wiped_by(resized), block);
+
+        // The block of a `Box` is handed over, unless it has to leave the pool.
+        let stacked: SmallBox<[u64; 4], S4> = SmallBox::new([1; 4]);
+        let mut boxed = None;
+        assert_eq!(
+            wiped_by(|| boxed = Some(SmallBox::into_box(stacked))),
+            mem::size_of::<S4>() + pooled
+        );
+        let heaped: SmallBox<[u64; 4], S2> = SmallBox::new([1; 4]);
+        assert_eq!(
+            wiped_by(|| boxed = Some(SmallBox::into_box(heaped))),
+            pooled
+        );
+        drop(boxed);
+
+        let stacked: SmallBox<dyn Any, S2> = smallbox!([1u64, 2]);
+        let mut downcasted = None;
+        assert_eq!(
+            wiped_by(|| downcasted = stacked.downcast::<[u64; 2]>().ok()),
+            space
+        );
+        assert_eq!(wiped_by(|| drop(downcasted)), space);
+        let heaped: SmallBox<dyn Any, S2> = smallbox!([1u64; 4]);
+        let mut downcasted = None;
+        assert_eq!(
+            wiped_by(|| downcasted = heaped.downcast::<[u64; 4]>().ok()),
+            0
+        );
+        assert_eq!(wiped_by(|| drop(downcasted)), block);
+    }
 }
@@ -34,3 +34,45 @@ pub struct S32 {
 pub struct S64 {
     _inner: [usize; 64],
 }
+
+/// Raises the alignment of `Space` to 16 bytes
+///
+/// A value whose alignment is larger than that of the space is always stored on the heap. The
+/// value cannot be placed at an aligned offset inside the space instead, because moving the
+/// [`SmallBox`](crate::SmallBox) would only preserve the alignment of the space and leave the
+/// value misaligned. Wrapping the space raises its alignment, so that over-aligned values can be
+/// stored inline.
+///
+/// # Example
+///
+/// ```
+/// use smallbox::SmallBox;
+/// use smallbox::space::Align16;
+/// use smallbox::space::S4;
+///
+/// #[repr(align(16))]
+/// struct Aligned([u8; 16]);
+///
+/// let aligned: SmallBox<_, Align16<S4>> = SmallBox::new(Aligned([0; 16]));
+/// assert!(!aligned.is_heap());
+/// ```
+#[repr(C, align(16))]
+pub struct Align16<Space> {
+    _inner: Space,
+}
+
+/// Raises the alignment of `Space` to 32 bytes
+///
+/// See [`Align16`] for why this is needed.
+#[repr(C, align(32))]
+pub struct Align32<Space> {
+    _inner: Space,
+}
+
+/// Raises the alignment of `Space` to 64 bytes
+///
+/// See [`Align16`] for why this is needed.
+#[repr(C, align(64))]
+pub struct Align64<Space> {
+    _inner: Space,
+}
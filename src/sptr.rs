@@ -5,6 +5,10 @@ mod implementation {
         core::ptr::without_provenance_mut(addr)
     }
 
+    pub fn addr<T: ?Sized>(ptr: *const T) -> usize {
+        ptr.addr()
+    }
+
     pub fn with_metadata_of<T: ?Sized, U: ?Sized>(ptr: *const T, meta: *const U) -> *const U {
         ptr.with_metadata_of(meta)
     }
@@ -30,6 +34,10 @@ mod implementation {
         }
     }
 
+    pub fn addr<T: ?Sized>(ptr: *const T) -> usize {
+        ptr.cast::<u8>() as usize
+    }
+
     pub fn with_metadata_of<T: ?Sized, U: ?Sized>(ptr: *const T, meta: *const U) -> *const U {
         with_metadata_of_mut(cast_to_mut(ptr), meta)
     }
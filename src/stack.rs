@@ -0,0 +1,574 @@
+//! Boxes whose inline space is a borrowed, caller-provided slot
+//!
+//! The inline space of a [`SmallBox`] lives inside the box, so moving the box copies the whole
+//! space. A [`StackBox`] instead places the value in a [`Slot`] borrowed from the caller and keeps
+//! only a pointer to it, so moving the handle never moves the value. Values that do not fit in
+//! the slot fall back to the heap.
+//!
+//! # Example
+//!
+//! ```
+//! #[macro_use]
+//! extern crate smallbox;
+//!
+//! # fn main() {
+//! use core::any::Any;
+//! use core::mem::MaybeUninit;
+//! use core::mem::size_of;
+//!
+//! use smallbox::space::S64;
+//! use smallbox::stack::StackBox;
+//!
+//! let mut slot = MaybeUninit::<S64>::uninit();
+//! let value: StackBox<dyn Any> = stackbox!(in &mut slot, [7u8; 256]);
+//!
+//! assert!(!value.is_heap());
+//! assert_eq!(size_of::<StackBox<dyn Any>>(), 3 * size_of::<usize>());
+//! assert_eq!(*value.downcast::<[u8; 256]>().unwrap(), [7; 256]);
+//! # }
+//! ```
+//!
+//! [`SmallBox`]: crate::SmallBox
+
+use core::alloc::Layout;
+use core::any::Any;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::Hash;
+use core::hash::{self};
+use core::marker::PhantomData;
+#[cfg(feature = "coerce")]
+use core::marker::Unsize;
+use core::mem::ManuallyDrop;
+use core::mem::MaybeUninit;
+use core::mem::{self};
+use core::ops;
+#[cfg(feature = "coerce")]
+use core::ops::CoerceUnsized;
+use core::ptr;
+use core::ptr::NonNull;
+use core::slice;
+
+use crate::heap;
+use crate::sptr;
+
+/// Memory that a [`StackBox`] can place its value in
+///
+/// Implemented for byte buffers and for [`MaybeUninit`] of any type, e.g. one of the spaces in
+/// [`crate::space`].
+pub trait Slot {
+    /// Returns the memory of the slot as bytes.
+    fn bytes_mut(&mut self) -> &mut [MaybeUninit<u8>];
+}
+
+impl Slot for [MaybeUninit<u8>] {
+    #[inline]
+    fn bytes_mut(&mut self) -> &mut [MaybeUninit<u8>] {
+        self
+    }
+}
+
+impl<const N: usize> Slot for [MaybeUninit<u8>; N] {
+    #[inline]
+    fn bytes_mut(&mut self) -> &mut [MaybeUninit<u8>] {
+        self
+    }
+}
+
+impl<S> Slot for MaybeUninit<S> {
+    #[inline]
+    fn bytes_mut(&mut self) -> &mut [MaybeUninit<u8>] {
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr().cast(), mem::size_of::<S>()) }
+    }
+}
+
+/// Box value in a borrowed slot or on heap depending on its size, as a [`StackBox`]
+///
+/// This macro is the [`StackBox`] counterpart of [`smallbox!`](crate::smallbox!), and follows
+/// the same coercion rules. The slot is passed first, after `in`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// extern crate smallbox;
+///
+/// # fn main() {
+/// use core::fmt::Display;
+/// use core::mem::MaybeUninit;
+///
+/// use smallbox::stack::StackBox;
+///
+/// let mut slot = [MaybeUninit::<u8>::uninit(); 16];
+/// let value: StackBox<dyn Display> = stackbox!(in &mut slot, 42u32);
+///
+/// assert!(!value.is_heap());
+/// assert_eq!(value.to_string(), "42");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! stackbox {
+    ( in $slot: expr, $e: expr ) => {{
+        let slot = $slot;
+        let val = $e;
+        let ptr = ::core::ptr::addr_of!(val);
+        #[allow(unsafe_code)]
+        unsafe {
+            $crate::stack::StackBox::new_unchecked_in(val, ptr, slot)
+        }
+    }};
+}
+
+/// A box that stores value in a borrowed slot or on heap depending on its size
+///
+/// Create one with [`StackBox::new_in`], or with [`stackbox!`] to coerce the value to an unsized
+/// type. The handle is a pointer and a flag, no matter how large the slot is.
+pub struct StackBox<'a, T: ?Sized> {
+    ptr: NonNull<T>,
+    heap: bool,
+    _phantom: PhantomData<(&'a mut [MaybeUninit<u8>], T)>,
+}
+
+#[cfg(feature = "coerce")]
+impl<'a, T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<StackBox<'a, U>> for StackBox<'a, T> {}
+
+impl<'a, T: ?Sized> StackBox<'a, T> {
+    /// Box value in `slot` or on heap depending on its size.
+    ///
+    /// The value is placed at the first suitably aligned address of the slot.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::mem::MaybeUninit;
+    ///
+    /// use smallbox::space::S4;
+    /// use smallbox::stack::StackBox;
+    ///
+    /// let mut slot = MaybeUninit::<S4>::uninit();
+    /// let small = StackBox::new_in([0usize; 2], &mut slot);
+    /// assert!(!small.is_heap());
+    ///
+    /// let mut slot = MaybeUninit::<S4>::uninit();
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
+    /// let large = StackBox::new_in([1usize; 8], &mut slot);
+    /// assert!(large.is_heap());
+    /// # }
+    /// ```
+    #[inline(always)]
+    pub fn new_in<S>(val: T, slot: &'a mut S) -> StackBox<'a, T>
+    where
+        T: Sized,
+        S: ?Sized + Slot,
+    {
+        stackbox!(in slot, val)
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub unsafe fn new_unchecked_in<U, S>(val: U, ptr: *const T, slot: &'a mut S) -> StackBox<'a, T>
+    where
+        U: Sized,
+        S: ?Sized + Slot,
+    {
+        let val = ManuallyDrop::new(val);
+        Self::new_copy::<U>(&val, ptr, slot.bytes_mut())
+    }
+
+    unsafe fn new_copy<U>(
+        val: &U,
+        metadata_ptr: *const T,
+        slot: &'a mut [MaybeUninit<u8>],
+    ) -> StackBox<'a, T>
+    where
+        U: ?Sized,
+    {
+        let layout = Layout::for_value::<U>(val);
+
+        let base = slot.as_mut_ptr().cast::<u8>();
+        let offset = base.align_offset(layout.align());
+        let fits = offset
+            .checked_add(layout.size())
+            .is_some_and(|end| end <= slot.len());
+
+        let dst: *mut u8 = if fits {
+            // Slot.
+            #[cfg(feature = "stats")]
+            crate::stats::record_inline();
+            base.add(offset)
+        } else if layout.size() == 0 {
+            // ZST, which will behave like being stored on heap but will not actually allocate.
+            #[cfg(feature = "stats")]
+            crate::stats::record_heap();
+            sptr::without_provenance_mut(layout.align())
+        } else {
+            // Heap.
+            #[cfg(feature = "stats")]
+            crate::stats::record_heap();
+            heap::alloc(layout)
+        };
+
+        ptr::copy_nonoverlapping(sptr::from_ref(val).cast(), dst, layout.size());
+
+        StackBox {
+            // Safety: dst is in the slot, a non-zero alignment or returned from the allocator and
+            // checked for null.
+            ptr: NonNull::new_unchecked(sptr::with_metadata_of_mut(dst, metadata_ptr)),
+            heap: !fits,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns true if data is allocated on heap.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::mem::MaybeUninit;
+    ///
+    /// use smallbox::stack::StackBox;
+    ///
+    /// let mut slot = [MaybeUninit::<u8>::uninit(); 8];
+    /// let in_slot = StackBox::new_in(0u32, &mut slot);
+    /// assert!(!in_slot.is_heap());
+    ///
+    /// let mut slot = [MaybeUninit::<u8>::uninit(); 8];
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
+    /// let heaped = StackBox::new_in([0u32; 4], &mut slot);
+    /// assert!(heaped.is_heap());
+    /// # }
+    /// ```
+    #[inline]
+    pub fn is_heap(&self) -> bool {
+        self.heap
+    }
+
+    /// Consumes the StackBox and returns ownership of the boxed value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::mem::MaybeUninit;
+    ///
+    /// use smallbox::space::S4;
+    /// use smallbox::stack::StackBox;
+    ///
+    /// let mut slot = MaybeUninit::<S4>::uninit();
+    /// let boxed = StackBox::new_in(vec![21, 56, 420], &mut slot);
+    /// let val = boxed.into_inner();
+    /// assert_eq!(val[1], 56);
+    /// ```
+    #[inline]
+    pub fn into_inner(self) -> T
+    where T: Sized {
+        let this = ManuallyDrop::new(self);
+        unsafe {
+            let ret_val = this.ptr.as_ptr().read();
+            this.dealloc();
+            ret_val
+        }
+    }
+
+    /// Deallocates the heap memory without dropping the boxed value.
+    ///
+    /// With the `zeroize` feature, the value is wiped from the slot instead.
+    unsafe fn dealloc(&self) {
+        let layout = Layout::for_value::<T>(self.ptr.as_ref());
+        if self.heap && layout.size() != 0 {
+            heap::dealloc(self.ptr.as_ptr().cast(), layout);
+        }
+        #[cfg(feature = "zeroize")]
+        if !self.heap {
+            crate::zeroize::wipe(self.ptr.as_ptr().cast(), layout.size());
+        }
+    }
+
+    unsafe fn downcast_unchecked<U: Any>(self) -> StackBox<'a, U> {
+        let this = ManuallyDrop::new(self);
+        StackBox {
+            ptr: this.ptr.cast(),
+            heap: this.heap,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a> StackBox<'a, dyn Any> {
+    /// Attempt to downcast the box to a concrete type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate smallbox;
+    ///
+    /// # fn main() {
+    /// use core::any::Any;
+    /// use core::mem::MaybeUninit;
+    ///
+    /// use smallbox::space::S1;
+    /// use smallbox::stack::StackBox;
+    ///
+    /// let mut slot = MaybeUninit::<S1>::uninit();
+    /// let value: StackBox<dyn Any> = stackbox!(in &mut slot, 42u32);
+    /// assert_eq!(*value.downcast::<u32>().unwrap(), 42);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn downcast<T: Any>(self) -> Result<StackBox<'a, T>, Self> {
+        if self.is::<T>() {
+            unsafe { Ok(self.downcast_unchecked()) }
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<'a> StackBox<'a, dyn Any + Send> {
+    /// Attempt to downcast the box to a concrete type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate smallbox;
+    ///
+    /// # fn main() {
+    /// use core::any::Any;
+    /// use core::mem::MaybeUninit;
+    ///
+    /// use smallbox::space::S1;
+    /// use smallbox::stack::StackBox;
+    ///
+    /// let mut slot = MaybeUninit::<S1>::uninit();
+    /// let value: StackBox<dyn Any + Send> = stackbox!(in &mut slot, 42u32);
+    /// assert_eq!(*value.downcast::<u32>().unwrap(), 42);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn downcast<T: Any>(self) -> Result<StackBox<'a, T>, Self> {
+        if self.is::<T>() {
+            unsafe { Ok(self.downcast_unchecked()) }
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<'a, T: ?Sized> ops::Deref for StackBox<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<'a, T: ?Sized> ops::DerefMut for StackBox<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<'a, T: ?Sized> ops::Drop for StackBox<'a, T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place::<T>(self.ptr.as_ptr());
+            self.dealloc();
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for StackBox<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for StackBox<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized + PartialEq> PartialEq for StackBox<'a, T> {
+    fn eq(&self, other: &StackBox<'a, T>) -> bool {
+        PartialEq::eq(&**self, &**other)
+    }
+}
+
+impl<'a, T: ?Sized + PartialOrd> PartialOrd for StackBox<'a, T> {
+    fn partial_cmp(&self, other: &StackBox<'a, T>) -> Option<Ordering> {
+        PartialOrd::partial_cmp(&**self, &**other)
+    }
+}
+
+impl<'a, T: ?Sized + Ord> Ord for StackBox<'a, T> {
+    fn cmp(&self, other: &StackBox<'a, T>) -> Ordering {
+        Ord::cmp(&**self, &**other)
+    }
+}
+
+impl<'a, T: ?Sized + Eq> Eq for StackBox<'a, T> {}
+
+impl<'a, T: ?Sized + Hash> Hash for StackBox<'a, T> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+unsafe impl<'a, T: ?Sized + Send> Send for StackBox<'a, T> {}
+unsafe impl<'a, T: ?Sized + Sync> Sync for StackBox<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use core::any::Any;
+    #[cfg(not(feature = "inline-only"))]
+    use core::cell::Cell;
+    use core::mem;
+    use core::mem::MaybeUninit;
+
+    #[cfg(not(feature = "inline-only"))]
+    use ::alloc::boxed::Box;
+
+    #[cfg(not(feature = "inline-only"))]
+    use super::Slot;
+    use super::StackBox;
+    use crate::space::*;
+
+    #[test]
+    fn test_size() {
+        assert_eq!(
+            mem::size_of::<StackBox<dyn Any>>(),
+            3 * mem::size_of::<usize>()
+        );
+        assert_eq!(
+            mem::size_of::<StackBox<[u8]>>(),
+            mem::size_of::<Option<StackBox<[u8]>>>()
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_stack() {
+        let mut slot = MaybeUninit::<S2>::uninit();
+        let stacked: StackBox<[usize]> = stackbox!(in &mut slot, [1usize, 2]);
+        assert!(!stacked.is_heap());
+        assert_eq!(*stacked, [1, 2]);
+
+        // moving the handle does not move the value
+        let addr = stacked.as_ptr();
+        let moved = Box::new(stacked);
+        assert_eq!(moved.as_ptr(), addr);
+        drop(moved);
+
+        let mut slot = MaybeUninit::<S2>::uninit();
+        let heaped: StackBox<[usize]> = stackbox!(in &mut slot, [1usize, 2, 3]);
+        assert!(heaped.is_heap());
+        assert_eq!(*heaped, [1, 2, 3]);
+
+        let mut slot = [MaybeUninit::<u8>::uninit(); 64];
+        let mut closure: StackBox<dyn FnMut() -> u8> = stackbox!(in &mut slot[..], {
+            let mut n = 0;
+            move || {
+                n += 1;
+                n
+            }
+        });
+        closure();
+        assert_eq!(closure(), 2);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_unaligned_slot() {
+        let mut slot = MaybeUninit::<[u64; 3]>::uninit();
+        let bytes = slot.bytes_mut();
+
+        // the value is aligned within the slot, so it no longer fits after skipping one byte
+        let stacked = StackBox::new_in([1u64, 2], &mut bytes[1..]);
+        assert!(!stacked.is_heap());
+        #[allow(clippy::as_conversions)]
+        let addr = &*stacked as *const [u64; 2] as usize;
+        assert_eq!(addr % mem::align_of::<u64>(), 0);
+        drop(stacked);
+
+        let heaped = StackBox::new_in([1u64, 2, 3], &mut bytes[1..]);
+        assert!(heaped.is_heap());
+        assert_eq!(*heaped, [1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_drop() {
+        #[allow(dead_code)]
+        struct Struct<'a>(&'a Cell<usize>, [u8; 24]);
+        impl<'a> Drop for Struct<'a> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        trait Dummy {}
+        impl<'a> Dummy for Struct<'a> {}
+
+        let count = Cell::new(0);
+
+        let mut slot = MaybeUninit::<S8>::uninit();
+        let stacked: StackBox<dyn Dummy> = stackbox!(in &mut slot, Struct(&count, [0; 24]));
+        assert!(!stacked.is_heap());
+        let mut slot = MaybeUninit::<S1>::uninit();
+        let heaped: StackBox<dyn Dummy> = stackbox!(in &mut slot, Struct(&count, [0; 24]));
+        assert!(heaped.is_heap());
+
+        drop(stacked);
+        drop(heaped);
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_downcast() {
+        let mut slot = MaybeUninit::<S1>::uninit();
+        let stacked: StackBox<dyn Any> = stackbox!(in &mut slot, 0x01u32);
+        assert_eq!(*stacked.downcast::<u32>().unwrap(), 0x01);
+
+        let mut slot = MaybeUninit::<S1>::uninit();
+        let heaped: StackBox<dyn Any + Send> = stackbox!(in &mut slot, [1u64; 4]);
+        let heaped = heaped.downcast::<u8>().unwrap_err();
+        assert_eq!(*heaped.downcast::<[u64; 4]>().unwrap(), [1; 4]);
+    }
+
+    #[test]
+    #[cfg(feature = "inline-only")]
+    #[should_panic(expected = "needs a heap fallback")]
+    fn test_no_alloc() {
+        let mut slot = MaybeUninit::<S1>::uninit();
+        let _ = StackBox::new_in([1u64, 2], &mut slot);
+    }
+
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn test_zeroize() {
+        let mut slot = MaybeUninit::<S1>::uninit();
+        let stacked: StackBox<u64> = StackBox::new_in(u64::MAX, &mut slot);
+        assert_eq!(stacked.into_inner(), u64::MAX);
+        assert_eq!(unsafe { slot.as_ptr().cast::<u64>().read() }, 0);
+
+        let mut slot = MaybeUninit::<S1>::uninit();
+        let stacked: StackBox<dyn Any> = stackbox!(in &mut slot, u64::MAX);
+        drop(stacked.downcast::<u64>().unwrap());
+        assert_eq!(unsafe { slot.as_ptr().cast::<u64>().read() }, 0);
+
+        #[cfg(not(feature = "inline-only"))]
+        {
+            use crate::zeroize::tests::wiped_by;
+
+            let mut slot = MaybeUninit::<S1>::uninit();
+            let heaped: StackBox<[u64]> = stackbox!(in &mut slot, [1u64; 4]);
+            assert!(heaped.is_heap());
+            assert_eq!(wiped_by(|| drop(heaped)), 32);
+        }
+    }
+}
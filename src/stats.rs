@@ -0,0 +1,144 @@
+//! Global counters of inline and heap placements
+//!
+//! This module is only available with the `stats` feature. The counters are updated with relaxed
+//! atomic operations by all box types in this crate and are shared by all threads, so they are
+//! meant for monitoring rather than exact accounting.
+//!
+//! # Example
+//!
+//! ```
+//! use smallbox::SmallBox;
+//! use smallbox::space::S1;
+//! use smallbox::stats;
+//!
+//! let before = stats::snapshot();
+//! let heaped: SmallBox<[usize; 2], S1> = SmallBox::new([0, 1]);
+//! let after = stats::snapshot();
+//!
+//! assert!(after.heap_constructions > before.heap_constructions);
+//! # drop(heaped);
+//! ```
+
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+
+static INLINE_CONSTRUCTIONS: AtomicUsize = AtomicUsize::new(0);
+static HEAP_CONSTRUCTIONS: AtomicUsize = AtomicUsize::new(0);
+static HEAP_BYTES_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static HEAP_BYTES_DEALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static RESIZE_PROMOTIONS: AtomicUsize = AtomicUsize::new(0);
+static INTO_BOX_PROMOTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// A copy of the global counters at one point in time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Number of values placed in inline space
+    pub inline_constructions: usize,
+    /// Number of values placed on the heap, including zero-sized values that need no allocation
+    pub heap_constructions: usize,
+    /// Number of heap bytes allocated, including allocations adopted with
+    /// [`SmallBox::from_box`](crate::SmallBox::from_box)
+    pub heap_bytes_allocated: usize,
+    /// Number of heap bytes deallocated, including allocations handed over with
+    /// [`SmallBox::into_box`](crate::SmallBox::into_box)
+    pub heap_bytes_deallocated: usize,
+    /// Number of inline values moved to the heap by [`SmallBox::resize`](crate::SmallBox::resize)
+    pub resize_promotions: usize,
+    /// Number of inline values moved to the heap by
+    /// [`SmallBox::into_box`](crate::SmallBox::into_box)
+    pub into_box_promotions: usize,
+}
+
+/// Returns the current values of the global counters.
+pub fn snapshot() -> Snapshot {
+    Snapshot {
+        inline_constructions: INLINE_CONSTRUCTIONS.load(Ordering::Relaxed),
+        heap_constructions: HEAP_CONSTRUCTIONS.load(Ordering::Relaxed),
+        heap_bytes_allocated: HEAP_BYTES_ALLOCATED.load(Ordering::Relaxed),
+        heap_bytes_deallocated: HEAP_BYTES_DEALLOCATED.load(Ordering::Relaxed),
+        resize_promotions: RESIZE_PROMOTIONS.load(Ordering::Relaxed),
+        into_box_promotions: INTO_BOX_PROMOTIONS.load(Ordering::Relaxed),
+    }
+}
+
+/// Resets all global counters to zero.
+pub fn reset() {
+    INLINE_CONSTRUCTIONS.store(0, Ordering::Relaxed);
+    HEAP_CONSTRUCTIONS.store(0, Ordering::Relaxed);
+    HEAP_BYTES_ALLOCATED.store(0, Ordering::Relaxed);
+    HEAP_BYTES_DEALLOCATED.store(0, Ordering::Relaxed);
+    RESIZE_PROMOTIONS.store(0, Ordering::Relaxed);
+    INTO_BOX_PROMOTIONS.store(0, Ordering::Relaxed);
+}
+
+#[inline]
+pub(crate) fn record_inline() {
+    INLINE_CONSTRUCTIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[inline]
+pub(crate) fn record_heap() {
+    HEAP_CONSTRUCTIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[inline]
+#[cfg(not(feature = "inline-only"))]
+pub(crate) fn record_alloc(size: usize) {
+    HEAP_BYTES_ALLOCATED.fetch_add(size, Ordering::Relaxed);
+}
+
+#[inline]
+#[cfg(not(feature = "inline-only"))]
+pub(crate) fn record_dealloc(size: usize) {
+    HEAP_BYTES_DEALLOCATED.fetch_add(size, Ordering::Relaxed);
+}
+
+#[inline]
+#[cfg(not(feature = "inline-only"))]
+pub(crate) fn record_adopt(size: usize) {
+    record_alloc(size);
+}
+
+#[inline]
+#[cfg(not(feature = "inline-only"))]
+pub(crate) fn record_release(size: usize) {
+    record_dealloc(size);
+}
+
+#[inline]
+pub(crate) fn record_resize_promotion() {
+    RESIZE_PROMOTIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[inline]
+#[cfg(not(feature = "inline-only"))]
+pub(crate) fn record_into_box_promotion() {
+    INTO_BOX_PROMOTIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(all(test, not(feature = "inline-only")))]
+mod tests {
+    use super::snapshot;
+    use crate::SmallBox;
+    use crate::space::*;
+
+    #[test]
+    fn test_counters() {
+        // Other tests run concurrently, so only lower bounds can be checked.
+        let before = snapshot();
+
+        let stacked: SmallBox<[usize; 2], S2> = SmallBox::new([0, 1]);
+        let heaped: SmallBox<[usize; 2], S1> = stacked.resize();
+        let boxed = SmallBox::into_box(SmallBox::<_, S4>::new(0usize));
+        drop(heaped);
+        drop(boxed);
+
+        let after = snapshot();
+        assert!(after.inline_constructions >= before.inline_constructions + 2);
+        assert!(after.heap_constructions >= before.heap_constructions + 2);
+        assert!(after.heap_bytes_allocated >= before.heap_bytes_allocated + 24);
+        assert!(after.heap_bytes_deallocated >= before.heap_bytes_deallocated + 24);
+        assert!(after.resize_promotions > before.resize_promotions);
+        assert!(after.into_box_promotions > before.into_box_promotions);
+    }
+}
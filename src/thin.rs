@@ -0,0 +1,664 @@
+use core::alloc::Layout;
+use core::any::Any;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::hint::unreachable_unchecked;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::mem::MaybeUninit;
+use core::mem::{self};
+use core::ops;
+use core::ptr;
+use core::ptr::NonNull;
+
+use crate::heap;
+use crate::smallbox::MIN_ALIGNMENT;
+use crate::sptr;
+
+/// Box value on stack or on heap depending on its size, as a [`ThinSmallBox`]
+///
+/// This macro is the [`ThinSmallBox`] counterpart of [`smallbox!`](crate::smallbox!), and follows
+/// the same coercion rules.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// extern crate smallbox;
+///
+/// # fn main() {
+/// use core::any::Any;
+///
+/// use smallbox::ThinSmallBox;
+/// use smallbox::space::*;
+///
+/// let small: ThinSmallBox<[usize], S4> = thin_smallbox!([0usize; 2]);
+/// let any: ThinSmallBox<dyn Any, S4> = thin_smallbox!(1u8);
+///
+/// assert_eq!(small.len(), 2);
+/// assert!(any.is::<u8>());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! thin_smallbox {
+    ( $e: expr ) => {{
+        let val = $e;
+        let ptr = ::core::ptr::addr_of!(val);
+        #[allow(unsafe_code)]
+        unsafe {
+            $crate::ThinSmallBox::new_unchecked(val, ptr)
+        }
+    }};
+}
+
+/// A [`SmallBox`] whose handle is only one word larger than its space
+///
+/// [`SmallBox<dyn Trait, Space>`] keeps a fat pointer next to the inline space, so the handle is
+/// two words larger than `Space`. `ThinSmallBox` instead stores the metadata of the value (the
+/// vtable or the length) in front of the value, in the inline space or in the heap allocation,
+/// and keeps only a thin pointer in the handle.
+///
+/// In exchange, the metadata of an unsized `T` takes up one word of the inline space, or as many
+/// bytes as the alignment of a value aligned to more than a word. `ThinSmallBox<dyn Trait, S2>`
+/// therefore holds a value of up to one word inline, like a `SmallBox<dyn Trait, S1>` with a
+/// handle of the same size. A sized `T` has no metadata, and the value uses the whole space.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// extern crate smallbox;
+///
+/// # fn main() {
+/// use core::fmt::Display;
+/// use core::mem::size_of;
+///
+/// use smallbox::ThinSmallBox;
+/// use smallbox::space::S4;
+///
+/// let value: ThinSmallBox<dyn Display, S4> = thin_smallbox!(42u32);
+///
+/// assert!(!value.is_heap());
+/// assert_eq!(value.to_string(), "42");
+/// assert_eq!(
+///     size_of::<ThinSmallBox<dyn Display, S4>>(),
+///     size_of::<S4>() + size_of::<usize>()
+/// );
+/// # }
+/// ```
+///
+/// [`SmallBox`]: crate::SmallBox
+/// [`SmallBox<dyn Trait, Space>`]: crate::SmallBox
+pub struct ThinSmallBox<T: ?Sized, Space> {
+    space: MaybeUninit<UnsafeCell<Space>>,
+    /// The address of a heap value, or for an inline value, its offset in the space plus one
+    ///
+    /// Heap values are at an even address, see `storage_layout`, so the two are told apart by
+    /// the lowest bit.
+    ptr: NonNull<u8>,
+    _phantom: PhantomData<T>,
+}
+
+/// The metadata of an unsized value, stored in the word in front of the value
+///
+/// Kept as `MaybeUninit` so that a vtable pointer keeps its provenance.
+type Header = MaybeUninit<*const ()>;
+
+impl<T: ?Sized, Space> ThinSmallBox<T, Space> {
+    /// Whether the storage has no header, which is the case for a sized `T`
+    const HEADERLESS: bool = mem::size_of::<*const T>() == mem::size_of::<*const u8>();
+
+    /// Box value on stack or on heap depending on its size.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::ThinSmallBox;
+    /// use smallbox::space::*;
+    ///
+    /// let small: ThinSmallBox<_, S4> = ThinSmallBox::new([0usize; 2]);
+    /// assert!(!small.is_heap());
+    ///
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
+    /// let large: ThinSmallBox<_, S4> = ThinSmallBox::new([1usize; 8]);
+    /// assert!(large.is_heap());
+    /// # }
+    /// ```
+    #[inline(always)]
+    pub fn new(val: T) -> ThinSmallBox<T, Space>
+    where T: Sized {
+        thin_smallbox!(val)
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub unsafe fn new_unchecked<U>(val: U, ptr: *const T) -> ThinSmallBox<T, Space>
+    where U: Sized {
+        #[cfg(feature = "inline-only")]
+        const {
+            // See `storage_layout`, the value follows a header if `T` is unsized.
+            let (offset, align) = if Self::HEADERLESS {
+                (0, mem::align_of::<U>())
+            } else {
+                let align = if mem::align_of::<U>() > mem::align_of::<Header>() {
+                    mem::align_of::<U>()
+                } else {
+                    mem::align_of::<Header>()
+                };
+                (align, align)
+            };
+            assert!(
+                (Self::HEADERLESS && mem::size_of::<U>() == 0)
+                    || (offset + mem::size_of::<U>() <= mem::size_of::<Space>()
+                        && align <= mem::align_of::<Space>()),
+                "the value does not fit in the space, and the `inline-only` feature disables the heap fallback"
+            )
+        }
+        let val = ManuallyDrop::new(val);
+        Self::new_copy(&val, ptr)
+    }
+
+    /// Returns true if data is allocated on heap.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::ThinSmallBox;
+    /// use smallbox::space::S2;
+    ///
+    /// let stacked: ThinSmallBox<(usize, usize), S2> = ThinSmallBox::new((0usize, 1usize));
+    /// assert!(!stacked.is_heap());
+    ///
+    /// # #[cfg(not(feature = "inline-only"))]
+    /// # {
+    /// let heaped: ThinSmallBox<[usize; 3], S2> = ThinSmallBox::new([0usize; 3]);
+    /// assert!(heaped.is_heap());
+    /// # }
+    /// ```
+    #[inline]
+    pub fn is_heap(&self) -> bool {
+        sptr::addr(self.ptr.as_ptr()) & 1 == 0
+    }
+
+    /// Consumes the ThinSmallBox and returns ownership of the boxed value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smallbox::ThinSmallBox;
+    /// use smallbox::space::S4;
+    ///
+    /// let boxed: ThinSmallBox<_, S4> = ThinSmallBox::new(vec![21, 56, 420]);
+    /// let val = boxed.into_inner();
+    /// assert_eq!(val[1], 56);
+    /// ```
+    #[inline]
+    pub fn into_inner(self) -> T
+    where T: Sized {
+        let this = ManuallyDrop::new(self);
+        unsafe {
+            let ret_val = this.as_ptr().read();
+            this.dealloc(Layout::new::<T>());
+            ret_val
+        }
+    }
+
+    /// Returns the layout of the storage for a value of `layout`, and the offset of the value
+    /// in the storage.
+    ///
+    /// If `T` is unsized, the metadata of the value is stored in the [`Header`] right in front of
+    /// the value, and the value is aligned to at least a word. Otherwise the storage is just the
+    /// value.
+    #[inline]
+    fn storage_layout(layout: Layout) -> (Layout, usize) {
+        if Self::HEADERLESS {
+            return (layout, 0);
+        }
+        let align = layout.align().max(mem::align_of::<Header>());
+        let size = layout.size().checked_add(align).expect("capacity overflow");
+        let storage = Layout::from_size_align(size, align).expect("capacity overflow");
+        (storage, align)
+    }
+
+    unsafe fn new_copy<U>(val: &U, metadata_ptr: *const T) -> ThinSmallBox<T, Space>
+    where U: ?Sized {
+        let layout = Layout::for_value::<U>(val);
+        let (storage_layout, offset) = Self::storage_layout(layout);
+        let space_layout = Layout::new::<Space>();
+
+        let mut space = MaybeUninit::<UnsafeCell<Space>>::uninit();
+
+        let (ptr_this, data): (*mut u8, *mut u8) = if storage_layout.size() <= space_layout.size()
+            && storage_layout.align() <= space_layout.align()
+        {
+            // Stack.
+            #[cfg(feature = "stats")]
+            crate::stats::record_inline();
+            (
+                sptr::without_provenance_mut(offset + 1),
+                space.as_mut_ptr().cast::<u8>().add(offset),
+            )
+        } else if storage_layout.size() == 0 {
+            // ZST, which will behave like being stored on heap but will not actually allocate.
+            #[cfg(feature = "stats")]
+            crate::stats::record_heap();
+            let dangling = sptr::without_provenance_mut(layout.align().max(MIN_ALIGNMENT));
+            (dangling, dangling)
+        } else {
+            // Heap.
+            let storage_layout = storage_layout
+                // Safety: MIN_ALIGNMENT is 2, which is a valid power-of-two alignment.
+                .align_to(MIN_ALIGNMENT)
+                .unwrap_or_else(|_| unreachable_unchecked());
+            #[cfg(feature = "stats")]
+            crate::stats::record_heap();
+            let data = heap::alloc(storage_layout).add(offset);
+            (data, data)
+        };
+
+        if !Self::HEADERLESS {
+            data.cast::<Header>()
+                .sub(1)
+                .write(Self::metadata(metadata_ptr));
+        }
+        ptr::copy_nonoverlapping(sptr::from_ref(val).cast(), data, layout.size());
+
+        ThinSmallBox {
+            space,
+            // Safety: ptr is either an odd inline tag, a non-zero alignment or returned from the
+            // allocator and checked for null.
+            ptr: NonNull::new_unchecked(ptr_this),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Deallocates the heap storage of a value of `layout`, without dropping the value.
+    ///
+    /// With the `zeroize` feature, inline storage is wiped instead.
+    unsafe fn dealloc(&self, layout: Layout) {
+        if self.is_heap() {
+            let (storage_layout, offset) = Self::storage_layout(layout);
+            if storage_layout.size() != 0 {
+                let storage_layout = storage_layout
+                    .align_to(MIN_ALIGNMENT)
+                    .unwrap_or_else(|_| unreachable_unchecked());
+                heap::dealloc(self.ptr.as_ptr().sub(offset), storage_layout);
+            }
+        } else {
+            #[cfg(feature = "zeroize")]
+            crate::zeroize::wipe(
+                UnsafeCell::raw_get(self.space.as_ptr()).cast(),
+                mem::size_of::<Space>(),
+            );
+        }
+    }
+
+    /// Returns the metadata of `ptr`, which must be a fat pointer.
+    ///
+    /// Like `sptr`, this relies on a fat pointer being the data pointer followed by the metadata.
+    #[inline]
+    unsafe fn metadata(ptr: *const T) -> Header {
+        sptr::from_ref(&ptr).cast::<Header>().add(1).read()
+    }
+
+    /// Builds a fat pointer from the address of a value and its metadata.
+    #[inline]
+    unsafe fn from_parts(data: *const u8, metadata: Header) -> *const T {
+        let mut ptr = MaybeUninit::<*const T>::uninit();
+        let words = ptr.as_mut_ptr().cast::<Header>();
+        words.write(MaybeUninit::new(data.cast()));
+        words.add(1).write(metadata);
+        ptr.assume_init()
+    }
+
+    unsafe fn downcast_unchecked<U: Any>(self) -> ThinSmallBox<U, Space> {
+        let this = ManuallyDrop::new(self);
+        let val = this.as_ptr().cast::<U>().read();
+        this.dealloc(Layout::new::<U>());
+        ThinSmallBox::new(val)
+    }
+
+    /// Returns the address of the value.
+    #[inline]
+    fn data(&self) -> *mut u8 {
+        if self.is_heap() {
+            self.ptr.as_ptr()
+        } else {
+            let offset = sptr::addr(self.ptr.as_ptr()) - 1;
+            // Safety: the offset of an inline value is within the space.
+            unsafe {
+                UnsafeCell::raw_get(self.space.as_ptr())
+                    .cast::<u8>()
+                    .add(offset)
+            }
+        }
+    }
+
+    #[inline]
+    unsafe fn as_ptr(&self) -> *const T {
+        let data = self.data();
+        if Self::HEADERLESS {
+            return mem::transmute_copy::<*mut u8, *const T>(&data);
+        }
+        Self::from_parts(data, data.cast::<Header>().sub(1).read())
+    }
+
+    #[inline]
+    unsafe fn as_mut_ptr(&mut self) -> *mut T {
+        let data = self.data();
+        if Self::HEADERLESS {
+            return mem::transmute_copy::<*mut u8, *mut T>(&data);
+        }
+        Self::from_parts(data, data.cast::<Header>().sub(1).read()).cast_mut()
+    }
+}
+
+impl<Space> ThinSmallBox<dyn Any, Space> {
+    /// Attempt to downcast the box to a concrete type.
+    ///
+    /// The value is moved into a new box, because the storage of a sized value has a different
+    /// layout.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate smallbox;
+    ///
+    /// # fn main() {
+    /// use core::any::Any;
+    ///
+    /// use smallbox::ThinSmallBox;
+    /// use smallbox::space::S2;
+    ///
+    /// let value: ThinSmallBox<dyn Any, S2> = thin_smallbox!(42u32);
+    /// assert_eq!(*value.downcast::<u32>().unwrap(), 42);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn downcast<T: Any>(self) -> Result<ThinSmallBox<T, Space>, Self> {
+        if self.is::<T>() {
+            unsafe { Ok(self.downcast_unchecked()) }
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<Space> ThinSmallBox<dyn Any + Send, Space> {
+    /// Attempt to downcast the box to a concrete type.
+    ///
+    /// The value is moved into a new box, because the storage of a sized value has a different
+    /// layout.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate smallbox;
+    ///
+    /// # fn main() {
+    /// use core::any::Any;
+    ///
+    /// use smallbox::ThinSmallBox;
+    /// use smallbox::space::S2;
+    ///
+    /// let value: ThinSmallBox<dyn Any + Send, S2> = thin_smallbox!(42u32);
+    /// assert_eq!(*value.downcast::<u32>().unwrap(), 42);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn downcast<T: Any>(self) -> Result<ThinSmallBox<T, Space>, Self> {
+        if self.is::<T>() {
+            unsafe { Ok(self.downcast_unchecked()) }
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<T: ?Sized, Space> ops::Deref for ThinSmallBox<T, Space> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<T: ?Sized, Space> ops::DerefMut for ThinSmallBox<T, Space> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.as_mut_ptr() }
+    }
+}
+
+impl<T: ?Sized, Space> ops::Drop for ThinSmallBox<T, Space> {
+    fn drop(&mut self) {
+        unsafe {
+            let layout = Layout::for_value::<T>(&*self);
+            ptr::drop_in_place::<T>(&mut **self);
+            self.dealloc(layout);
+        }
+    }
+}
+
+impl<T: ?Sized + fmt::Display, Space> fmt::Display for ThinSmallBox<T, Space> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, Space> fmt::Debug for ThinSmallBox<T, Space> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+unsafe impl<T: ?Sized + Send, Space> Send for ThinSmallBox<T, Space> {}
+unsafe impl<T: ?Sized + Sync, Space> Sync for ThinSmallBox<T, Space> {}
+
+#[cfg(test)]
+mod tests {
+    use core::any::Any;
+    use core::mem;
+
+    use super::ThinSmallBox;
+    use crate::space::*;
+    use crate::sptr;
+
+    #[test]
+    fn test_size() {
+        assert_eq!(
+            mem::size_of::<ThinSmallBox<dyn Any, S2>>(),
+            mem::size_of::<S2>() + mem::size_of::<usize>()
+        );
+        assert_eq!(
+            mem::size_of::<ThinSmallBox<[u8], S2>>(),
+            mem::size_of::<Option<ThinSmallBox<[u8], S2>>>()
+        );
+    }
+
+    #[test]
+    fn test_space() {
+        let sized: ThinSmallBox<[usize; 2], S2> = ThinSmallBox::new([1, 2]);
+        assert!(!sized.is_heap());
+        assert_eq!(*sized, [1, 2]);
+        assert_eq!(sized.into_inner(), [1, 2]);
+
+        // The header takes one word of `S2`, which leaves room for one more
+        let empty: ThinSmallBox<[usize], S2> = thin_smallbox!([0usize; 0]);
+        assert!(!empty.is_heap());
+        assert!(empty.is_empty());
+
+        let slice: ThinSmallBox<[usize], S2> = thin_smallbox!([1usize]);
+        assert!(!slice.is_heap());
+        assert_eq!(*slice, [1]);
+
+        let any: ThinSmallBox<dyn Any, S2> = thin_smallbox!(1usize);
+        assert!(!any.is_heap());
+        assert_eq!(*any.downcast::<usize>().unwrap(), 1);
+
+        let slice: ThinSmallBox<[usize], S4> = thin_smallbox!([1usize, 2]);
+        assert!(!slice.is_heap());
+        assert_eq!(*slice, [1, 2]);
+
+        let sized: ThinSmallBox<[usize; 4], S4> = ThinSmallBox::new([1, 2, 3, 4]);
+        assert!(!sized.is_heap());
+        assert_eq!(*sized, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_space_heap() {
+        let sized: ThinSmallBox<[usize; 3], S2> = ThinSmallBox::new([1, 2, 3]);
+        assert!(sized.is_heap());
+        assert_eq!(sized.into_inner(), [1, 2, 3]);
+
+        let slice: ThinSmallBox<[usize], S2> = thin_smallbox!([1usize, 2]);
+        assert!(slice.is_heap());
+        assert_eq!(*slice, [1, 2]);
+
+        let slice: ThinSmallBox<[usize], S4> = thin_smallbox!([1usize, 2, 3, 4]);
+        assert!(slice.is_heap());
+        assert_eq!(*slice, [1, 2, 3, 4]);
+
+        let any: ThinSmallBox<dyn Any, S2> = thin_smallbox!([1usize, 2]);
+        assert!(any.is_heap());
+        let sized = any.downcast::<[usize; 2]>().unwrap();
+        assert!(!sized.is_heap());
+        assert_eq!(*sized, [1, 2]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_thin() {
+        let stacked: ThinSmallBox<[usize], S4> = thin_smallbox!([1usize, 2]);
+        assert!(!stacked.is_heap());
+        assert_eq!(*stacked, [1, 2]);
+
+        let heaped: ThinSmallBox<[usize], S4> = thin_smallbox!([1usize, 2, 3, 4]);
+        assert!(heaped.is_heap());
+        assert_eq!(*heaped, [1, 2, 3, 4]);
+
+        let mut closure: ThinSmallBox<dyn FnMut() -> u8, S4> = thin_smallbox!({
+            let mut n = 0;
+            move || {
+                n += 1;
+                n
+            }
+        });
+        closure();
+        assert_eq!(closure(), 2);
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_drop() {
+        use core::cell::Cell;
+
+        #[allow(dead_code)]
+        struct Struct<'a>(&'a Cell<bool>, [u8; 24]);
+        impl<'a> Drop for Struct<'a> {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        trait Dummy {}
+        impl<'a> Dummy for Struct<'a> {}
+
+        let flag = Cell::new(false);
+        let stacked: ThinSmallBox<dyn Dummy, S8> = thin_smallbox!(Struct(&flag, [0; 24]));
+        assert!(!stacked.is_heap());
+        drop(stacked);
+        assert!(flag.get());
+
+        let flag = Cell::new(false);
+        let heaped: ThinSmallBox<dyn Dummy, S4> = thin_smallbox!(Struct(&flag, [0; 24]));
+        assert!(heaped.is_heap());
+        drop(heaped);
+        assert!(flag.get());
+    }
+
+    #[test]
+    fn test_zst_over_aligned() {
+        #[repr(align(64))]
+        #[derive(Debug, PartialEq)]
+        struct Z;
+
+        let zst: ThinSmallBox<Z, S1> = ThinSmallBox::new(Z);
+        assert!(zst.is_heap());
+        assert_eq!(sptr::addr(&*zst) % 64, 0);
+        assert_eq!(zst.into_inner(), Z);
+        drop(ThinSmallBox::<Z, S1>::new(Z));
+
+        #[cfg(not(feature = "inline-only"))]
+        {
+            let any: ThinSmallBox<dyn Any, S1> = thin_smallbox!(Z);
+            assert!(any.is_heap());
+            assert_eq!(sptr::addr(&*any) % 64, 0);
+            let zst = any.downcast::<Z>().unwrap();
+            assert_eq!(*zst, Z);
+
+            let any: ThinSmallBox<dyn Any, S1> = thin_smallbox!(Z);
+            drop(any);
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "inline-only"))]
+    fn test_downcast() {
+        let stacked: ThinSmallBox<dyn Any, S4> = thin_smallbox!(0x01u32);
+        assert!(!stacked.is_heap());
+        assert_eq!(*stacked.downcast::<u32>().unwrap(), 0x01);
+
+        let heaped: ThinSmallBox<dyn Any + Send, S2> = thin_smallbox!([1usize, 2]);
+        assert!(heaped.is_heap());
+        assert_eq!(*heaped.downcast::<[usize; 2]>().unwrap(), [1, 2]);
+
+        let mismatched: ThinSmallBox<dyn Any, S1> = thin_smallbox!(0x01u32);
+        assert!(mismatched.downcast::<u8>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn test_zeroize() {
+        use crate::zeroize::tests::wiped_by;
+
+        let stacked: ThinSmallBox<[u64; 2], S2> = ThinSmallBox::new([1, 2]);
+        assert_eq!(
+            wiped_by(|| assert_eq!(stacked.into_inner(), [1, 2])),
+            mem::size_of::<S2>()
+        );
+
+        let stacked: ThinSmallBox<dyn Any, S4> = thin_smallbox!(1u64);
+        assert!(!stacked.is_heap());
+        let mut downcasted = None;
+        assert_eq!(
+            wiped_by(|| downcasted = stacked.downcast::<u64>().ok()),
+            mem::size_of::<S4>()
+        );
+        assert_eq!(wiped_by(|| drop(downcasted)), mem::size_of::<S4>());
+
+        // Heap blocks hold the header of an unsized value as well
+        #[cfg(not(feature = "inline-only"))]
+        {
+            let header = mem::size_of::<usize>();
+
+            let heaped: ThinSmallBox<[u64], S2> = thin_smallbox!([1u64; 3]);
+            assert!(heaped.is_heap());
+            assert_eq!(wiped_by(|| drop(heaped)), header + 24);
+
+            let heaped: ThinSmallBox<[u64; 4], S2> = ThinSmallBox::new([1; 4]);
+            assert_eq!(wiped_by(|| assert_eq!(heaped.into_inner(), [1; 4])), 32);
+
+            let heaped: ThinSmallBox<dyn Any, S2> = thin_smallbox!([1u64; 4]);
+            let mut downcasted = None;
+            assert_eq!(
+                wiped_by(|| downcasted = heaped.downcast::<[u64; 4]>().ok()),
+                header + 32
+            );
+            assert_eq!(wiped_by(|| drop(downcasted)), 32);
+        }
+    }
+}
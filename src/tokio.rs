@@ -0,0 +1,142 @@
+//! `tokio` support, enabled by the `tokio` feature
+//!
+//! [`SmallBox<T, Space>`] forwards tokio's `AsyncRead`, `AsyncWrite`, `AsyncBufRead` and
+//! `AsyncSeek` to the boxed value. Like the `Future` impl, they project the pinned box to its
+//! pinned value, so `T` does not have to be `Unpin`.
+//!
+//! [`SmallBox<T, Space>`]: crate::SmallBox
+
+use core::pin::Pin;
+use core::task::Context;
+use core::task::Poll;
+use std::io;
+
+use ::tokio::io::AsyncBufRead;
+use ::tokio::io::AsyncRead;
+use ::tokio::io::AsyncSeek;
+use ::tokio::io::AsyncWrite;
+use ::tokio::io::ReadBuf;
+
+use crate::SmallBox;
+
+impl<R: AsyncRead + ?Sized, Space, P> AsyncRead for SmallBox<R, Space, P> {
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.project().poll_read(cx, buf)
+    }
+}
+
+impl<W: AsyncWrite + ?Sized, Space, P> AsyncWrite for SmallBox<W, Space, P> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.project().poll_write_vectored(cx, bufs)
+    }
+
+    #[inline]
+    fn is_write_vectored(&self) -> bool {
+        (**self).is_write_vectored()
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().poll_shutdown(cx)
+    }
+}
+
+impl<B: AsyncBufRead + ?Sized, Space, P> AsyncBufRead for SmallBox<B, Space, P> {
+    #[inline]
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.project().poll_fill_buf(cx)
+    }
+
+    #[inline]
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.project().consume(amt)
+    }
+}
+
+impl<S: AsyncSeek + ?Sized, Space, P> AsyncSeek for SmallBox<S, Space, P> {
+    #[inline]
+    fn start_seek(self: Pin<&mut Self>, position: io::SeekFrom) -> io::Result<()> {
+        self.project().start_seek(position)
+    }
+
+    #[inline]
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        self.project().poll_complete(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::poll_fn;
+    use core::pin::Pin;
+    use std::io::Cursor;
+    use std::io::SeekFrom;
+
+    use ::tokio::io::AsyncBufRead;
+    use ::tokio::io::AsyncRead;
+    use ::tokio::io::AsyncSeek;
+    use ::tokio::io::AsyncWrite;
+    use ::tokio::io::ReadBuf;
+    use futures::executor::block_on;
+
+    use crate::SmallBox;
+    use crate::smallbox;
+    use crate::space::*;
+
+    #[test]
+    fn test_async_io() {
+        let mut reader: SmallBox<dyn AsyncBufRead + Send + Unpin, S4> =
+            smallbox!(Cursor::new(b"abc".to_vec()));
+        assert!(!reader.is_heap());
+        let mut reader = Pin::new(&mut reader);
+        let filled = block_on(poll_fn(|cx| {
+            reader.as_mut().poll_fill_buf(cx).map_ok(|buf| buf.to_vec())
+        }));
+        assert_eq!(filled.unwrap(), b"abc");
+        reader.as_mut().consume(1);
+
+        let mut reader: SmallBox<dyn AsyncRead + Unpin, S4> = smallbox!(&b"abc"[1..]);
+        let mut buf = [0; 4];
+        let mut read_buf = ReadBuf::new(&mut buf);
+        block_on(poll_fn(|cx| {
+            Pin::new(&mut reader).poll_read(cx, &mut read_buf)
+        }))
+        .unwrap();
+        assert_eq!(read_buf.filled(), b"bc");
+
+        let mut writer: SmallBox<_, S4> = SmallBox::new(Cursor::new(Vec::new()));
+        let mut pinned = Pin::new(&mut writer);
+        let written = block_on(poll_fn(|cx| pinned.as_mut().poll_write(cx, b"xyz")));
+        assert_eq!(written.unwrap(), 3);
+        pinned.as_mut().start_seek(SeekFrom::Start(1)).unwrap();
+        let pos = block_on(poll_fn(|cx| pinned.as_mut().poll_complete(cx)));
+        assert_eq!(pos.unwrap(), 1);
+        block_on(poll_fn(|cx| pinned.as_mut().poll_write(cx, b"Y"))).unwrap();
+        block_on(poll_fn(|cx| pinned.as_mut().poll_shutdown(cx))).unwrap();
+        assert_eq!(writer.into_inner().into_inner(), b"xYz");
+    }
+}
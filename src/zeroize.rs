@@ -0,0 +1,59 @@
+//! Wiping of memory that held a value, for the `zeroize` feature
+
+use core::ptr;
+use core::sync::atomic;
+use core::sync::atomic::Ordering;
+
+/// Overwrites `len` bytes at `ptr` with zeros
+///
+/// The writes are volatile, so they are not optimized away even though the memory is about to be
+/// freed or is never read again.
+#[inline]
+pub(crate) unsafe fn wipe(ptr: *mut u8, len: usize) {
+    for i in 0..len {
+        ptr::write_volatile(ptr.add(i), 0);
+    }
+    atomic::compiler_fence(Ordering::SeqCst);
+    #[cfg(test)]
+    tests::record(len);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    extern crate std;
+
+    use core::cell::Cell;
+
+    use super::wipe;
+
+    std::thread_local! {
+        /// The number of bytes wiped on the current thread
+        static WIPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    pub(super) fn record(len: usize) {
+        WIPED.with(|wiped| wiped.set(wiped.get() + len));
+    }
+
+    /// Returns the number of bytes wiped on the current thread while running `f`
+    ///
+    /// Moved-out and freed storage can not be read, so the tests of the boxes check that the
+    /// expected number of bytes was wiped instead.
+    pub(crate) fn wiped_by(f: impl FnOnce()) -> usize {
+        let before = WIPED.with(Cell::get);
+        f();
+        WIPED.with(Cell::get) - before
+    }
+
+    #[test]
+    fn test_wipe() {
+        let mut buf = [0xAAu8; 24];
+        assert_eq!(
+            wiped_by(|| unsafe { wipe(buf.as_mut_ptr().add(4), 16) }),
+            16
+        );
+        assert_eq!(buf[..4], [0xAA; 4]);
+        assert_eq!(buf[4..20], [0; 16]);
+        assert_eq!(buf[20..], [0xAA; 4]);
+    }
+}